@@ -1,8 +1,9 @@
+use anyhow::anyhow;
 use anyhow::Result;
-use colored::Colorize;
-use dbc::database::create_connection;
-use dbc::ui::{DbcClient, Helper, Opt};
-use dirs::home_dir;
+use dbc::ui::history::HistoryEntry;
+use dbc::ui::session::Session;
+use dbc::ui::theme::Role;
+use dbc::ui::{Command, Helper, Opt};
 use regex::Regex;
 use rustyline::error::ReadlineError;
 use rustyline::history::DefaultHistory;
@@ -12,87 +13,268 @@ use structopt::StructOpt;
 fn main() -> Result<()> {
     let opt = Opt::from_args();
 
-    let config = dbc::config::read_config()?;
-    let mut client = DbcClient::default();
+    if let Some(cmd) = opt.cmd {
+        return match cmd {
+            Command::Vault(c) => dbc::commands::vault::execute_vault_command(c),
+            Command::Config(c) => dbc::commands::config::execute_config_command(c),
+            Command::Fanout {
+                format,
+                group,
+                statement,
+            } => dbc::commands::fanout::execute_fanout_command(&group, &statement, format),
+            Command::Unknown(args) => Err(anyhow!("Unexpected arguments {:?}", args)),
+        };
+    }
 
-    let params = config.get(&opt.identifier).expect("No such identifier");
+    let identifier = match opt.identifier {
+        Some(i) => i,
+        None => {
+            Opt::clap().print_help()?;
+            println!();
+            return Ok(());
+        }
+    };
 
+    let config = dbc::config::read_config()?;
+    dbc::ui::theme::define_themes(config.themes.clone());
     if !opt.quiet {
-        println!("{}", "*".repeat(60).cyan());
-        println!("{} {:56} {}", "*".blue(), "Welcome to dbc", "*".blue());
-        println!("{}", "*".repeat(60).cyan());
-        println!("{}", "");
+        println!("{}", Role::Info.paint("*".repeat(60)));
+        println!(
+            "{} {:56} {}",
+            Role::Info.paint("*"),
+            "Welcome to dbc",
+            Role::Info.paint("*")
+        );
+        println!("{}", Role::Info.paint("*".repeat(60)));
+        println!();
     }
 
-    let mut conn = create_connection(&opt.identifier, params.clone())?;
-    conn.print_connection_info()?;
-
-    let tables = if opt.cache {
-        println!("{}", "Reading DB schema...".yellow());
-        conn.list_tables()?
-    } else {
-        vec![]
-    };
+    let mut sessions = vec![Session::open(&config, &identifier, &identifier, opt.cache)?];
+    let mut active = 0;
 
-    let completions: Vec<String> = tables.iter().map(|x| x.name.clone()).collect();
-    let query_completions: Vec<String> = conn
-        .standard_queries()
-        .iter()
-        .map(|q| q.name.to_string())
-        .collect();
-    let command_completions: Vec<String> =
-        vec!["export".into(), "list".into(), "all".into(), "set".into()];
+    let command_completions: Vec<String> = vec![
+        "export".into(),
+        "list".into(),
+        "all".into(),
+        "set".into(),
+        "explain".into(),
+        "history".into(),
+        "refresh".into(),
+        "connect".into(),
+        "connections".into(),
+        "use".into(),
+        "fanout".into(),
+    ];
 
     let helper = Helper {
-        completions: completions,
-        query_completions: query_completions,
-        command_completions: command_completions,
+        conn: sessions[0].conn.clone(),
+        dialect: sessions[0].dialect(),
+        schema: sessions[0].schema.clone(),
+        query_completions: sessions[0].query_completions.clone(),
+        command_completions,
+        connection_names: vec![identifier.clone()],
+        identifiers: config.connections.keys().cloned().collect(),
     };
     let mut rl = Editor::<Helper, DefaultHistory>::new()?;
     rl.set_helper(Some(helper));
-    let history_file = home_dir().unwrap().join(".dbc_history");
-    if rl.load_history(&history_file).is_err() {
-        println!("No previous history.");
+    for e in sessions[0].history.entries() {
+        rl.add_history_entry(e.line.as_str())?;
     }
+    let set_re = Regex::new(r":set (\S+) (.+)$").unwrap();
+    let desc_re = Regex::new(r":desc (\S+)$").unwrap();
+    let search_re = Regex::new(r":search (\S+)$").unwrap();
+    let export_re = Regex::new(r":export (\S+) (\S+)$").unwrap();
+    let explain_re = Regex::new(r"(?is)^:explain\s+(analyze\s+)?(.+)$").unwrap();
+    let history_run_re = Regex::new(r"^:history run (\d+)$").unwrap();
+    let history_re = Regex::new(r"^:history(?: (.+))?$").unwrap();
+    let connect_re = Regex::new(r"^:connect (\S+)(?: as (\S+))?$").unwrap();
+    let use_re = Regex::new(r"^:use (\S+)$").unwrap();
+    let fanout_re = Regex::new(r"(?is)^:fanout\s+(\S+)\s+(.+)$").unwrap();
+    dbc::ui::interrupt::install()?;
     loop {
-        let readline = rl.readline(&conn.prompt());
+        if let Some(e) = rl.helper().unwrap().schema.borrow_mut().take_error() {
+            println!("{}: {}", Role::Error.paint("Cannot load schema"), e);
+        }
+        let prompt = {
+            let s = &sessions[active];
+            s.client.prompt(s.conn.borrow().as_ref())
+        };
+        let readline = rl.readline(&prompt);
         match readline {
             Ok(line) => {
+                let line = match history_run_re.captures(line.trim()) {
+                    Some(c) => match c[1]
+                        .parse()
+                        .ok()
+                        .and_then(|n| sessions[active].history.get(n))
+                    {
+                        Some(e) if !e.line.starts_with(":history") => {
+                            println!("{}", Role::Muted.paint(&e.line));
+                            e.line.clone()
+                        }
+                        Some(_) => {
+                            println!(
+                                "{}",
+                                Role::Error.paint("Cannot run :history from the history")
+                            );
+                            continue;
+                        }
+                        None => {
+                            println!("No history entry {}", &c[1]);
+                            continue;
+                        }
+                    },
+                    None => line,
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
                 rl.add_history_entry(line.as_str())?;
 
+                // Commands changing the active connection, recorded in the history of
+                // the connection they were entered in
+                let previous = active;
+                let trimmed = line.trim();
+                let session_command = if trimmed == ":connections" {
+                    dbc::commands::connections::print_connections(&sessions, active);
+                    true
+                } else if trimmed.starts_with(":connect") {
+                    match connect_re.captures(trimmed) {
+                        Some(c) => {
+                            let name = c.get(2).map(|n| n.as_str()).unwrap_or(&c[1]);
+                            match sessions.iter().position(|s| s.name == name) {
+                                Some(i) => {
+                                    println!(
+                                        "{}",
+                                        Role::Warning.paint(format!("{} is already open.", name))
+                                    );
+                                    active = i;
+                                }
+                                None => match Session::open(&config, &c[1], name, opt.cache) {
+                                    Ok(s) => {
+                                        sessions.push(s);
+                                        active = sessions.len() - 1;
+                                    }
+                                    Err(e) => {
+                                        println!("{}: {:#}", Role::Error.paint("Cannot connect"), e)
+                                    }
+                                },
+                            }
+                        }
+                        None => println!("Usage: :connect <identifier> [as <name>]"),
+                    }
+                    true
+                } else if trimmed.starts_with(":use") {
+                    match use_re.captures(trimmed) {
+                        Some(c) => match sessions.iter().position(|s| s.name == c[1]) {
+                            Some(i) => active = i,
+                            None => println!(
+                                "{}",
+                                Role::Error.paint(format!("No open connection {}", &c[1]))
+                            ),
+                        },
+                        None => println!("Usage: :use <name>"),
+                    }
+                    true
+                } else {
+                    false
+                };
+                if session_command {
+                    let entry = HistoryEntry::new(&line, None);
+                    if let Err(e) = sessions[previous].history.append(entry) {
+                        println!("{}: {:#}", Role::Error.paint("Cannot save history"), e);
+                    }
+                    if active != previous {
+                        activate(&mut rl, &sessions, active)?;
+                    }
+                    continue;
+                }
+
+                let Session {
+                    client,
+                    history,
+                    conn: shared_conn,
+                    schema,
+                    ..
+                } = &mut sessions[active];
+                let mut conn = shared_conn.borrow_mut();
                 if line.starts_with(":") {
                     if line.starts_with(":set") {
-                        let re = Regex::new(r":set (\S+) (\S+)$").unwrap();
-                        if let Some(c) = re.captures(&line) {
-                            if &c[1] == "column_limit" {
-                                client.options.set_column_limit(c[2].parse()?);
-                            }
-                            if &c[1] == "row_limit" {
-                                client.options.set_row_limit(c[2].parse()?);
+                        if let Some(c) = set_re.captures(&line) {
+                            let res = dbc::commands::settings::execute_set(
+                                client, &mut conn, &c[1], &c[2],
+                            );
+                            if let Err(e) = res {
+                                println!("{}: {}", Role::Error.paint("Cannot set option"), e);
                             }
+                        } else if line.trim() == ":set" {
+                            dbc::commands::settings::print_settings(client);
+                        } else {
+                            println!("Usage: :set <option> <value>");
                         }
                     } else if line.starts_with(":desc") {
-                        let re = Regex::new(r":desc (\S+)$").unwrap();
-                        if let Some(c) = re.captures(&line) {
+                        if let Some(c) = desc_re.captures(&line) {
                             conn.describe(&c[1])?;
                         } else {
-                            println!("{}", "Usage: :desc <object>");
+                            println!("Usage: :desc <object>");
                         }
                     } else if line.starts_with(":search") {
-                        let re = Regex::new(r":search (\S+)$").unwrap();
-                        if let Some(c) = re.captures(&line) {
+                        if let Some(c) = search_re.captures(&line) {
                             conn.search(&c[1])?;
                         } else {
-                            println!("{}", "Usage: :search <object>");
+                            println!("Usage: :search <object>");
+                        }
+                    } else if line.starts_with(":explain") {
+                        if let Some(c) = explain_re.captures(&line) {
+                            let res = dbc::commands::explain::execute_explain(
+                                client,
+                                &mut conn,
+                                &c[2],
+                                c.get(1).is_some(),
+                            );
+                            if let Err(e) = res {
+                                println!(
+                                    "{}: {:#}",
+                                    Role::Error.paint("Cannot explain statement"),
+                                    e
+                                );
+                            }
+                        } else {
+                            println!("Usage: :explain [analyze] <statement>");
+                        }
+                    } else if line.starts_with(":history") {
+                        match history_re.captures(line.trim()) {
+                            Some(c) => dbc::commands::history::print_history(
+                                history,
+                                c.get(1).map(|p| p.as_str()),
+                            ),
+                            None => println!("Usage: :history [pattern] | :history run <n>"),
+                        }
+                    } else if line.starts_with(":fanout") {
+                        if let Some(c) = fanout_re.captures(&line) {
+                            let limit = client.options.row_limit;
+                            let res = dbc::commands::fanout::execute_fanout(
+                                client, &config, &c[1], &c[2], limit,
+                            );
+                            if let Err(e) = res {
+                                println!("{}: {:#}", Role::Error.paint("Cannot fan out"), e);
+                            }
+                        } else {
+                            println!("Usage: :fanout <group> <statement>");
+                        }
+                    } else if line.trim() == ":refresh" {
+                        let mut schema = schema.borrow_mut();
+                        match schema.refresh(&mut **conn) {
+                            Ok(()) => println!("{}", Role::Warning.paint("Reloading DB schema...")),
+                            Err(e) => {
+                                println!("{}: {:#}", Role::Error.paint("Cannot load schema"), e)
+                            }
                         }
                     } else if line.starts_with(":list") {
                         let last_line = client.last_select.clone();
                         match last_line {
                             Some(l) => dbc::commands::query::execute_query_and_print_results(
-                                &mut client,
-                                &mut conn,
-                                &l,
-                                1,
+                                client, &mut conn, &l, 1,
                             )?,
                             None => println!("No last query."),
                         }
@@ -100,16 +282,12 @@ fn main() -> Result<()> {
                         let last_line = client.last_select.clone();
                         match last_line {
                             Some(l) => dbc::commands::query::execute_query_and_print_results(
-                                &mut client,
-                                &mut conn,
-                                &l,
-                                1000,
+                                client, &mut conn, &l, 1000,
                             )?,
                             None => println!("No last query."),
                         }
                     } else if line.starts_with(":export") {
-                        let re = Regex::new(r":export (\S+) (\S+)$").unwrap();
-                        if let Some(c) = re.captures(&line) {
+                        if let Some(c) = export_re.captures(&line) {
                             let last_line = client.last_select.clone();
                             match last_line {
                                 Some(l) => {
@@ -120,68 +298,63 @@ fn main() -> Result<()> {
                                     };
                                     if &c[1] == "csv" {
                                         dbc::commands::export::execute_query_to_csv(
-                                            &mut client,
-                                            &mut conn,
-                                            &l,
-                                            f,
+                                            client, &mut conn, &l, f,
                                         )?;
                                     } else if &c[1] == "insert" {
                                         dbc::commands::export::execute_query_to_insert(
-                                            &mut client,
-                                            &mut conn,
-                                            &l,
-                                            f,
+                                            client, &mut conn, &l, f,
                                         )?;
                                     } else if &c[1] == "xls" || &c[1] == "excel" {
                                         dbc::commands::export::execute_query_to_excel(
-                                            &mut client,
+                                            client,
                                             &mut conn,
                                             &l,
                                             f.expect("Export of Excel to stdout not supported"),
                                         )?;
                                     } else {
-                                        println!("{}", "Format not supported".red());
+                                        println!("{}", Role::Error.paint("Format not supported"));
                                     }
                                 }
                                 None => println!("No last query."),
                             }
                         } else {
-                            println!("{}", "Usage: :export <format> <file>");
+                            println!("Usage: :export <format> <file>");
                         }
                     } else {
-                        println!("{}", "ERROR: Unsupported command".red());
+                        println!("{}", Role::Error.paint("ERROR: Unsupported command"));
+                    }
+                } else if let Some(input) = line.strip_prefix('@') {
+                    let res =
+                        dbc::commands::queries::execute_standard_query(client, &mut conn, input);
+                    if let Err(e) = res {
+                        println!("{}: {:#}", Role::Error.paint("Cannot run query"), e);
                     }
-                } else if line.starts_with("@") {
-                    let q = {
-                        let queries = conn.standard_queries();
-                        let v = queries.into_iter().filter(|x| x.name == &line[1..]).nth(0);
-                        v.map(|x| x.query.to_string())
-                    };
-                    match q {
-                        Some(x) => dbc::commands::query::execute_query_and_print_results(
-                            &mut client,
-                            &mut conn,
-                            &x,
-                            1000,
-                        )?,
-                        None => println!("Query not found {}", &line[1..]),
-                    };
                 } else {
                     let limit = client.options.row_limit;
                     let res = dbc::commands::query::execute_query_and_print_results(
-                        &mut client,
-                        &mut conn,
-                        &line,
-                        limit,
+                        client, &mut conn, &line, limit,
                     );
-                    match res {
-                        Err(e) => println!("{}: {}", "Cannot execute statement:".red(), e),
-                        Ok(_) => {}
+                    if let Err(e) = res {
+                        println!("{}: {}", Role::Error.paint("Cannot execute statement:"), e)
+                    }
+                }
+
+                let summary = client.last_execution.take();
+                if summary.as_ref().map(|s| s.ddl).unwrap_or(false) {
+                    let mut schema = schema.borrow_mut();
+                    if let Err(e) = schema.schema_changed(&mut **conn) {
+                        println!("{}: {:#}", Role::Error.paint("Cannot load schema"), e);
                     }
                 }
+                client.update_prompt(&mut **conn, summary.as_ref());
+                let entry = HistoryEntry::new(&line, summary);
+                if let Err(e) = history.append(entry) {
+                    println!("{}: {:#}", Role::Error.paint("Cannot save history"), e);
+                }
             }
             Err(ReadlineError::Interrupted) => {
-                break;
+                // Discards the line, dbc is left with Ctrl-D
+                continue;
             }
             Err(ReadlineError::Eof) => {
                 break;
@@ -192,11 +365,26 @@ fn main() -> Result<()> {
             }
         }
     }
-    rl.save_history(&history_file).unwrap();
-
     if !opt.quiet {
         println!();
-        println!("{}", "Thank you for using dbc.");
+        println!("Thank you for using dbc.");
     }
     Ok(())
 }
+
+/// Makes a connection the one input goes to: its completions, history and theme
+fn activate(
+    rl: &mut Editor<Helper, DefaultHistory>,
+    sessions: &[Session],
+    active: usize,
+) -> Result<()> {
+    let session = &sessions[active];
+    let helper = rl.helper_mut().unwrap();
+    helper.activate(session);
+    helper.connection_names = sessions.iter().map(|s| s.name.clone()).collect();
+    rl.clear_history()?;
+    for e in session.history.entries() {
+        rl.add_history_entry(e.line.as_str())?;
+    }
+    dbc::ui::theme::use_theme(&session.client.options.theme)
+}
@@ -0,0 +1,176 @@
+use crate::config::{read_config, Config};
+use crate::database::ConnectionParams;
+use crate::ui::theme::{print_table, Role};
+use crate::ui::ConfigCommand;
+use anyhow::anyhow;
+use anyhow::Result;
+use prettytable::format;
+use prettytable::{Cell, Row, Table};
+use serde_yaml::Value;
+
+/// Keys whose values are never printed
+const SECRET_KEYS: &[&str] = &["password", "sslpassword"];
+
+pub fn execute_config_command(cmd: ConfigCommand) -> Result<()> {
+    let config = read_config()?;
+    match cmd {
+        ConfigCommand::List => list(&config),
+        ConfigCommand::Show { identifier } => show(&config, &identifier),
+        ConfigCommand::Validate => validate(&config),
+    }
+}
+
+fn title(s: &str) -> Cell {
+    Role::Title.cell(s)
+}
+
+fn list(config: &Config) -> Result<()> {
+    println!("{}", Role::Heading.paint("Config files:"));
+    for f in &config.files {
+        println!("  {}", f.display());
+    }
+
+    println!("{}", Role::Heading.paint("Connections:"));
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(Row::new(vec![
+        title("identifier"),
+        title("type"),
+        title("url"),
+        title("defined in"),
+    ]));
+    for (identifier, values) in &config.values {
+        let origin = config
+            .origins
+            .get(identifier)
+            .and_then(|o| o.get("url").or_else(|| o.values().next()))
+            .map(|o| o.file.display().to_string())
+            .unwrap_or_default();
+        table.add_row(Row::new(vec![
+            Cell::new(identifier),
+            Cell::new(&display_value(values.get("type"))),
+            Cell::new(&display_value(values.get("url"))),
+            Cell::new(&origin),
+        ]));
+    }
+    print_table(&table);
+    Ok(())
+}
+
+fn show(config: &Config, identifier: &str) -> Result<()> {
+    let values = config
+        .values
+        .get(identifier)
+        .ok_or_else(|| anyhow!("No such identifier {}", identifier))?;
+    let origins = &config.origins[identifier];
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(Row::new(vec![title("key"), title("value"), title("from")]));
+    for (k, v) in values {
+        let key = display_value(Some(k));
+        let value = if SECRET_KEYS.contains(&key.as_str()) {
+            "********".to_string()
+        } else {
+            display_value(Some(v))
+        };
+        let origin = origins.get(&key).map(|o| o.to_string()).unwrap_or_default();
+        table.add_row(Row::new(vec![
+            Cell::new(&key),
+            Cell::new(&value),
+            Cell::new(&origin),
+        ]));
+    }
+    print_table(&table);
+
+    if let Some(e) = config.invalid.get(identifier) {
+        println!("{}: {}", Role::Error.paint("Error"), e);
+    }
+    Ok(())
+}
+
+fn validate(config: &Config) -> Result<()> {
+    let known = match serde_yaml::to_value(ConnectionParams::default())? {
+        Value::Mapping(m) => m,
+        _ => unreachable!(),
+    };
+
+    let mut errors = 0;
+    for (identifier, e) in &config.invalid {
+        println!(
+            "{} {}: {}",
+            Role::Identifier.paint(identifier),
+            Role::Error.paint("error"),
+            e
+        );
+        errors += 1;
+    }
+    for (identifier, params) in &config.connections {
+        for k in config.values[identifier].keys() {
+            if !known.contains_key(k) && !matches!(k.as_str(), Some("settings" | "queries")) {
+                println!(
+                    "{} {}: unknown key {}",
+                    Role::Identifier.paint(identifier),
+                    Role::Warning.paint("warning"),
+                    display_value(Some(k))
+                );
+            }
+        }
+        let type_ = params.type_.clone().unwrap_or_else(|| "ora".into());
+        if !matches!(
+            type_.as_ref(),
+            "pg" | "postgresql" | "sqlite" | "ora" | "oracle" | "mysql"
+        ) {
+            println!(
+                "{} {}: unknown type {}",
+                Role::Identifier.paint(identifier),
+                Role::Error.paint("error"),
+                type_
+            );
+            errors += 1;
+        }
+        if params.url.is_none() {
+            println!(
+                "{} {}: no url given",
+                Role::Identifier.paint(identifier),
+                Role::Warning.paint("warning")
+            );
+        }
+    }
+    for (group, members) in &config.groups {
+        for m in members {
+            if !config.connections.contains_key(m) && !config.invalid.contains_key(m) {
+                println!(
+                    "{} {}: unknown connection {}",
+                    Role::Identifier.paint(group),
+                    Role::Error.paint("error"),
+                    m
+                );
+                errors += 1;
+            }
+        }
+    }
+
+    if errors > 0 {
+        Err(anyhow!("{} errors in the configuration", errors))
+    } else {
+        println!(
+            "{}",
+            Role::Success.paint(format!(
+                "{} connections are valid.",
+                config.connections.len()
+            ))
+        );
+        Ok(())
+    }
+}
+
+fn display_value(v: Option<&Value>) -> String {
+    match v {
+        None | Some(Value::Null) => "".into(),
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => serde_yaml::to_string(v)
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default(),
+    }
+}
@@ -0,0 +1,31 @@
+use crate::ui::session::Session;
+use crate::ui::theme::{print_table, Role};
+use prettytable::format;
+use prettytable::{Cell, Row, Table};
+
+/// Handles `:connections`, listing the open connections with the active one marked
+pub fn print_connections(sessions: &[Session], active: usize) {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(Row::new(vec![
+        Role::Title.cell(""),
+        Role::Title.cell("name"),
+        Role::Title.cell("identifier"),
+        Role::Title.cell("backend"),
+    ]));
+    for (i, s) in sessions.iter().enumerate() {
+        let marker = if i == active { "*" } else { "" };
+        let name = if s.client.options.production {
+            Role::Production.cell(&s.name)
+        } else {
+            Role::Identifier.cell(&s.name)
+        };
+        table.add_row(Row::new(vec![
+            Role::Success.cell(marker),
+            name,
+            Cell::new(&s.identifier),
+            Role::Backend.cell(s.dialect().short_name()),
+        ]));
+    }
+    print_table(&table);
+}
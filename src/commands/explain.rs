@@ -0,0 +1,132 @@
+use crate::database::{Connection, PlanNode};
+use crate::sql::classify::strip_terminator;
+use crate::sql::{classify, split_statements};
+use crate::ui::interrupt::CancelGuard;
+use crate::ui::theme::Role;
+use crate::ui::DbcClient;
+use anyhow::anyhow;
+use anyhow::Result;
+use colored::Colorize;
+
+/// How many nodes are highlighted as the most expensive ones
+const HOT_NODES: usize = 3;
+
+/// Handles `:explain [analyze] <statement>`
+pub fn execute_explain(
+    client: &mut DbcClient,
+    conn: &mut Box<dyn Connection>,
+    statement: &str,
+    analyze: bool,
+) -> Result<()> {
+    let dialect = conn.dialect();
+    // Postgres and MySQL would run every statement after the first one
+    let statement = match split_statements(statement, dialect)[..] {
+        [s] => strip_terminator(s, dialect),
+        _ => return Err(anyhow!("Only one statement can be explained at a time")),
+    };
+    if !super::safeguard::check_statement(client, &classify(statement, dialect))? {
+        println!("{}", Role::Warning.paint("Statement not executed."));
+        return Ok(());
+    }
+
+    let plan = {
+        let _guard = CancelGuard::new(conn.cancel_handle());
+        conn.explain(statement, analyze)?
+    };
+
+    let mut costs = vec![];
+    own_costs(&plan, &mut costs);
+    let mut hot: Vec<(usize, f64)> = costs
+        .into_iter()
+        .enumerate()
+        .filter(|(_, c)| *c > 0.0)
+        .collect();
+    hot.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let hot: Vec<usize> = hot.into_iter().take(HOT_NODES).map(|(i, _)| i).collect();
+
+    print_node(&plan, "", "", &hot, &mut 0);
+    Ok(())
+}
+
+/// Collects the time (or if not analyzed, the cost) of each node without its
+/// children, in the order the nodes are printed
+fn own_costs(node: &PlanNode, costs: &mut Vec<f64>) {
+    let total = |n: &PlanNode| n.actual_time.or(n.cost).unwrap_or(0.0);
+    let children: f64 = node.children.iter().map(total).sum();
+    costs.push(total(node) - children);
+    for c in &node.children {
+        own_costs(c, costs);
+    }
+}
+
+fn print_node(node: &PlanNode, prefix: &str, child_prefix: &str, hot: &[usize], index: &mut usize) {
+    let operation = if hot.contains(index) {
+        Role::Mark.paint(&node.operation)
+    } else {
+        Role::Strong.paint(&node.operation)
+    };
+    *index += 1;
+
+    let mut figures = vec![];
+    if let Some(c) = node.cost {
+        figures.push(format!("cost={:.2}", c).normal());
+    }
+    if let Some(r) = node.rows {
+        figures.push(format!("rows={}", r).normal());
+    }
+    if let Some(a) = node.actual_rows {
+        let actual = format!("actual rows={}", a);
+        figures.push(match node.rows {
+            Some(r) if misestimated(r, a) => Role::Warning.paint(actual),
+            _ => actual.normal(),
+        });
+    }
+    if let Some(t) = node.actual_time {
+        figures.push(format!("time={:.3}ms", t).normal());
+    }
+    let figures: Vec<String> = figures.iter().map(|f| f.to_string()).collect();
+    if figures.is_empty() {
+        println!("{}{}", Role::Info.paint(prefix), operation);
+    } else {
+        println!(
+            "{}{}  {}",
+            Role::Info.paint(prefix),
+            operation,
+            Role::Muted.paint(figures.join(" "))
+        );
+    }
+
+    let detail_prefix = if node.children.is_empty() {
+        format!("{}   ", child_prefix)
+    } else {
+        format!("{}│  ", child_prefix)
+    };
+    for d in &node.details {
+        println!(
+            "{}{}",
+            Role::Info.paint(&detail_prefix),
+            Role::Detail.paint(d)
+        );
+    }
+
+    for (i, c) in node.children.iter().enumerate() {
+        let last = i + 1 == node.children.len();
+        print_node(
+            c,
+            &format!("{}{}", child_prefix, if last { "└─ " } else { "├─ " }),
+            &format!("{}{}", child_prefix, if last { "   " } else { "│  " }),
+            hot,
+            index,
+        );
+    }
+}
+
+/// The estimate is off by a factor of ten or more
+fn misestimated(estimate: f64, actual: f64) -> bool {
+    let (low, high) = if estimate < actual {
+        (estimate, actual)
+    } else {
+        (actual, estimate)
+    };
+    high >= 10.0 * low.max(1.0)
+}
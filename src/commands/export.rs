@@ -1,8 +1,8 @@
 use crate::database::Connection;
+use crate::sql::classify::main_table;
 use crate::ui::DbcClient;
 use anyhow::Result;
 use csv::WriterBuilder;
-use regex::Regex;
 use simple_excel_writer::*;
 use std::fs::File;
 use std::io::stdout;
@@ -14,7 +14,7 @@ pub fn execute_query_to_csv(
     query: &str,
     export_filename: Option<String>,
 ) -> Result<()> {
-    let res = conn.query(&query)?;
+    let res = conn.query(query)?;
     let mut builder = WriterBuilder::new();
     let builder = builder.delimiter(b';');
     match export_filename {
@@ -52,12 +52,8 @@ pub fn execute_query_to_insert(
     query: &str,
     export_filename: Option<String>,
 ) -> Result<()> {
-    let res = conn.query(&query)?;
-    let re = Regex::new(r"\s+from\s+([a-z0-9_]+)\b").unwrap();
-    let table_name = match re.captures(query) {
-        Some(caps) => String::from(&caps[1]),
-        None => "xxxxx".into(),
-    };
+    let res = conn.query(query)?;
+    let table_name = main_table(query, conn.dialect()).unwrap_or_else(|| "xxxxx".into());
 
     let mut writer: Box<dyn Write> = match export_filename {
         Some(f) => Box::new(File::create(f)?),
@@ -74,7 +70,7 @@ pub fn execute_query_to_insert(
         let values = row
             .data
             .iter()
-            .map(|v| value(v))
+            .map(value)
             .collect::<Vec<String>>()
             .join(", ");
         writeln!(
@@ -98,7 +94,7 @@ pub fn execute_query_to_excel(
     let mut workbook = Workbook::create(&export_filename);
     let mut sheet = workbook.create_sheet("Data");
 
-    let res = conn.query(&query)?;
+    let res = conn.query(query)?;
 
     workbook.write_sheet(&mut sheet, |sheet_writer| {
         let sw = sheet_writer;
@@ -0,0 +1,309 @@
+use super::query::print_result;
+use super::safeguard::{confirm, dangerous_reason};
+use super::settings::{apply_connection_settings, apply_display_settings};
+use crate::config::{ClientSettings, Config};
+use crate::database::{create_connection, resolve_params, CancelHandle, Column};
+use crate::database::{ConnectionParams, Outcome, QueryResult, Row};
+use crate::sql::classify::strip_terminator;
+use crate::sql::{classify, split_statements};
+use crate::ui::interrupt::CancelGuard;
+use crate::ui::theme::Role;
+use crate::ui::{DbcClient, ExecutionSummary, OutputFormat, Pager};
+use anyhow::anyhow;
+use anyhow::Result;
+use std::io::{stdin, Read};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+/// What a statement returned on one connection of the group
+struct Answer {
+    identifier: String,
+    outcomes: Result<Vec<Outcome>>,
+    messages: Vec<String>,
+}
+
+/// Handles `:fanout <group> <statement>`: executes the statement on all connections
+/// of the group in parallel and prints their rows in one table, with the connection
+/// in the first column. A failing connection is reported without stopping the others.
+pub fn execute_fanout(
+    client: &mut DbcClient,
+    config: &Config,
+    group: &str,
+    statement: &str,
+    row_limit: usize,
+) -> Result<()> {
+    let members = config.group(group)?;
+    let mut answers = vec![];
+    let mut jobs = vec![];
+    let mut dangerous = None;
+    for identifier in members.iter().cloned() {
+        let prepared = config.connection(&identifier).and_then(|p| {
+            let dialect = p.dialect()?;
+            let settings = config.settings_for(&identifier);
+            for s in split_statements(statement, dialect) {
+                let classification = classify(strip_terminator(s, dialect), dialect);
+                if settings.read_only.unwrap_or(false) && classification.violates_read_only() {
+                    return Err(anyhow!(
+                        "Connection is read-only, refusing to execute {}",
+                        classification.keyword
+                    ));
+                }
+                if settings
+                    .confirm_writes
+                    .unwrap_or_else(|| p.has_tag("production"))
+                {
+                    dangerous = dangerous.or_else(|| dangerous_reason(&classification));
+                }
+            }
+            // Passwords may be prompted for, which cannot happen in parallel
+            Ok((resolve_params(&identifier, p)?, settings))
+        });
+        match prepared {
+            Ok((params, settings)) => jobs.push((identifier, params, settings)),
+            Err(e) => answers.push(Answer {
+                identifier,
+                outcomes: Err(e),
+                messages: vec![],
+            }),
+        }
+    }
+    if let Some(reason) = dangerous {
+        let question = format!("Really execute {} on {} connections?", reason, jobs.len());
+        if !confirm(&question)? {
+            println!("{}", Role::Warning.paint("Statement not executed."));
+            client.last_execution = Some(ExecutionSummary {
+                error: Some("Statement not executed".into()),
+                ..ExecutionSummary::default()
+            });
+            return Ok(());
+        }
+    }
+
+    // Ctrl-C cancels the statement on every connection that got that far
+    let cancels: Arc<Mutex<Vec<CancelHandle>>> = Arc::default();
+    let _guard = {
+        let cancels = cancels.clone();
+        CancelGuard::new(Box::new(move || {
+            for cancel in cancels.lock().unwrap().iter() {
+                cancel()?;
+            }
+            Ok(())
+        }))
+    };
+    let start = Instant::now();
+    answers.extend(thread::scope(|scope| {
+        let handles: Vec<_> = jobs
+            .into_iter()
+            .map(|(identifier, params, settings)| {
+                let cancels = cancels.clone();
+                scope.spawn(move || run_on(identifier, params, &settings, statement, &cancels))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("Fanout thread panicked"))
+            .collect::<Vec<_>>()
+    }));
+    let elapsed = start.elapsed();
+    answers.sort_by_key(|a| members.iter().position(|m| *m == a.identifier));
+    print_answers(client, answers, row_limit, elapsed.as_secs_f64())?;
+    if let Some(s) = client.last_execution.as_mut() {
+        s.duration = elapsed;
+    }
+    Ok(())
+}
+
+/// Connects and executes the statement, from a thread of its own
+fn run_on(
+    identifier: String,
+    params: ConnectionParams,
+    settings: &ClientSettings,
+    statement: &str,
+    cancels: &Mutex<Vec<CancelHandle>>,
+) -> Answer {
+    let mut messages = vec![];
+    let run = || -> Result<Vec<Outcome>> {
+        let mut conn = create_connection(&identifier, params)?;
+        cancels.lock().unwrap().push(conn.cancel_handle());
+        // The same session as in the REPL, startup_sql without printing its results
+        apply_connection_settings(&mut DbcClient::default(), &mut conn, settings, false)?;
+        let dialect = conn.dialect();
+        for s in settings.startup_sql.iter().flatten() {
+            conn.run(strip_terminator(s, dialect))?;
+        }
+        // One by one, as they were checked
+        let mut outcomes = vec![];
+        let mut res = Ok(());
+        for s in split_statements(statement, dialect) {
+            match conn.run(strip_terminator(s, dialect)) {
+                Ok(o) => outcomes.extend(o),
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+        }
+        messages = conn.take_messages().unwrap_or_default();
+        // Nobody can commit after this, the open transaction ends with the connection
+        if !conn.autocommit() && outcomes.iter().any(|o| matches!(o, Outcome::Updated(_))) {
+            messages.push("Autocommit is off, changes are rolled back.".into());
+        }
+        res.map(|_| outcomes)
+    };
+    let outcomes = run();
+    Answer {
+        identifier,
+        outcomes,
+        messages,
+    }
+}
+
+/// Merges the result sets into one table, followed by what else the connections returned
+fn print_answers(
+    client: &mut DbcClient,
+    answers: Vec<Answer>,
+    row_limit: usize,
+    seconds: f64,
+) -> Result<()> {
+    let total = answers.len();
+    let mut merged: Option<QueryResult> = None;
+    let mut rows = 0;
+    let mut feedback = vec![];
+    let mut errors = vec![];
+    for a in answers {
+        let Answer {
+            identifier,
+            outcomes,
+            messages,
+        } = a;
+        let outcomes = match outcomes {
+            Ok(o) => o,
+            Err(e) => {
+                errors.push((identifier, format!("{:#}", e)));
+                continue;
+            }
+        };
+        for outcome in outcomes {
+            match outcome {
+                Outcome::Rows { result, .. } => {
+                    let names: Vec<&str> = result.columns.iter().map(|c| c.name.as_str()).collect();
+                    let merged = merged.get_or_insert_with(|| QueryResult {
+                        columns: std::iter::once("connection")
+                            .chain(names.iter().copied())
+                            .map(|n| Column { name: n.into() })
+                            .collect(),
+                        rows: vec![],
+                    });
+                    if !merged.columns[1..]
+                        .iter()
+                        .map(|c| c.name.as_str())
+                        .eq(names.iter().copied())
+                    {
+                        errors.push((
+                            identifier.clone(),
+                            format!("Other columns than the first result: {}", names.join(", ")),
+                        ));
+                        continue;
+                    }
+                    rows += result.rows.len() as u64;
+                    merged.rows.extend(result.rows.into_iter().map(|r| {
+                        Row {
+                            data: std::iter::once(Some(identifier.clone()))
+                                .chain(r.data)
+                                .collect(),
+                        }
+                    }));
+                }
+                Outcome::Updated(n) => {
+                    rows += n;
+                    feedback.push((identifier.clone(), format!("{} rows updated.", n)));
+                }
+                Outcome::Message(m) => feedback.push((identifier.clone(), m)),
+            }
+        }
+        for m in messages {
+            println!(
+                "{}: {}",
+                Role::Identifier.paint(&identifier),
+                Role::Detail.paint(m)
+            );
+        }
+    }
+
+    if let Some(merged) = &merged {
+        print_result(client, merged, row_limit)?;
+    }
+    for (identifier, f) in &feedback {
+        println!(
+            "{}: {}",
+            Role::Identifier.paint(identifier),
+            Role::Feedback.paint(f)
+        );
+    }
+    for (identifier, e) in &errors {
+        println!(
+            "{}: {}: {}",
+            Role::Identifier.paint(identifier),
+            Role::Error.paint("Error"),
+            e
+        );
+    }
+    if !errors.is_empty() {
+        println!(
+            "{}",
+            Role::Warning.paint(format!("{} of {} connections failed.", errors.len(), total))
+        );
+    }
+    if client.options.timing {
+        println!(
+            "{}",
+            Role::Info.paint(format!(
+                "{} connections in {:.3}s, {} rows.",
+                total, seconds, rows
+            ))
+        );
+    }
+    client.last_execution = Some(ExecutionSummary {
+        rows,
+        error: if errors.is_empty() {
+            None
+        } else {
+            Some(format!("{} of {} connections failed", errors.len(), total))
+        },
+        ..ExecutionSummary::default()
+    });
+    Ok(())
+}
+
+/// Runs `dbc fanout <group> <statement>` without a session; `-` reads the
+/// statement from stdin. Fails if any connection failed.
+pub fn execute_fanout_command(
+    group: &str,
+    statement: &str,
+    format: Option<OutputFormat>,
+) -> Result<()> {
+    let config = crate::config::read_config()?;
+    crate::ui::theme::define_themes(config.themes.clone());
+    let mut client = DbcClient::default();
+    apply_display_settings(&mut client, &config.settings)?;
+    if let Some(f) = format {
+        client.options.format = f;
+    }
+    // The output is read by scripts more often than scrolled through
+    client.options.pager = Pager::Off;
+
+    let statement = if statement == "-" {
+        let mut s = String::new();
+        stdin().read_to_string(&mut s)?;
+        s
+    } else {
+        statement.to_string()
+    };
+    crate::ui::interrupt::install()?;
+    execute_fanout(&mut client, &config, group, &statement, usize::MAX)?;
+    match client.last_execution.and_then(|s| s.error) {
+        Some(e) => Err(anyhow!(e)),
+        None => Ok(()),
+    }
+}
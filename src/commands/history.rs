@@ -0,0 +1,53 @@
+use crate::ui::history::History;
+use crate::ui::theme::Role;
+use chrono::DateTime;
+
+/// How many of the matching entries `:history` prints
+const HISTORY_LIMIT: usize = 50;
+
+/// Handles `:history [pattern]`, listing the latest entries containing the pattern
+pub fn print_history(history: &History, pattern: Option<&str>) {
+    let pattern = pattern.map(|p| p.to_lowercase());
+    let matches: Vec<_> = history
+        .entries()
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| match &pattern {
+            Some(p) => e.line.to_lowercase().contains(p),
+            None => true,
+        })
+        .collect();
+    if matches.is_empty() {
+        println!("{}", Role::Warning.paint("No history entries."));
+        return;
+    }
+
+    let skipped = matches.len().saturating_sub(HISTORY_LIMIT);
+    for (i, e) in &matches[skipped..] {
+        let time = DateTime::parse_from_rfc3339(&e.time)
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|_| e.time.clone());
+        let outcome = match (&e.error, e.duration_ms, e.rows) {
+            (Some(err), _, _) => Role::Error
+                .paint(err.lines().next().unwrap_or_default())
+                .to_string(),
+            (None, Some(d), Some(r)) => Role::Success
+                .paint(format!("{}ms, {} rows", d, r))
+                .to_string(),
+            _ => String::new(),
+        };
+        println!(
+            "{:>5}  {}  {}  {}",
+            Role::Identifier.paint((i + 1).to_string()),
+            Role::Muted.paint(time),
+            e.line.split_whitespace().collect::<Vec<_>>().join(" "),
+            outcome
+        );
+    }
+    if skipped > 0 {
+        println!(
+            "{}",
+            Role::Warning.paint(format!("{} older entries not shown.", skipped))
+        );
+    }
+}
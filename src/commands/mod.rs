@@ -1,2 +1,11 @@
+pub mod config;
+pub mod connections;
+pub mod explain;
 pub mod export;
+pub mod fanout;
+pub mod history;
+pub mod queries;
 pub mod query;
+pub mod safeguard;
+pub mod settings;
+pub mod vault;
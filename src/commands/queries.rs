@@ -0,0 +1,325 @@
+use crate::database::{Connection, Dialect, ParamType, QueryParam, StandardQuery};
+use crate::sql::{tokenize, TokenKind};
+use crate::ui::theme::{print_table, Role};
+use crate::ui::DbcClient;
+use anyhow::anyhow;
+use anyhow::Result;
+use chrono::NaiveDate;
+use prettytable::format;
+use prettytable::{Cell, Row, Table};
+use std::collections::BTreeMap;
+
+/// Rows printed for standard queries
+const ROW_LIMIT: usize = 1000;
+
+/// Handles `@name [args]` and `@?`
+///
+/// Arguments are given in the order of the parameters or as `name=value`,
+/// values with spaces can be quoted.
+pub fn execute_standard_query(
+    client: &mut DbcClient,
+    conn: &mut Box<dyn Connection>,
+    input: &str,
+) -> Result<()> {
+    let input = input.trim();
+    if input == "?" {
+        print_queries(&conn.standard_queries(), conn.dialect());
+        return Ok(());
+    }
+    let (name, args) = match input.split_once(char::is_whitespace) {
+        Some((n, a)) => (n, a),
+        None => (input, ""),
+    };
+    let query = conn
+        .standard_queries()
+        .into_iter()
+        .find(|q| q.name == name)
+        .ok_or_else(|| anyhow!("Query not found {}", name))?;
+    let dialect = conn.dialect();
+    let sql = bind_arguments(&query, &split_arguments(args)?, dialect)?;
+    super::query::execute_query_and_print_results(client, conn, &sql, ROW_LIMIT)
+}
+
+fn print_queries(queries: &[StandardQuery], dialect: Dialect) {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(Row::new(
+        ["name", "parameters", "description"]
+            .iter()
+            .map(|t| Role::Title.cell(t))
+            .collect(),
+    ));
+    for q in queries {
+        let params: Vec<String> = parameters(q, dialect)
+            .iter()
+            .map(|p| {
+                let mut s = format!("{}: {:?}", p.name, p.type_).to_lowercase();
+                if let Some(d) = &p.default {
+                    s.push_str(&format!(" = {}", d));
+                }
+                s
+            })
+            .collect();
+        table.add_row(Row::new(vec![
+            Cell::new(&format!("@{}", q.name)),
+            Cell::new(&params.join("\n")),
+            Cell::new(q.description.as_deref().unwrap_or("")),
+        ]));
+    }
+    print_table(&table);
+}
+
+/// Splits the arguments at whitespace, except inside single or double quotes
+fn split_arguments(args: &str) -> Result<Vec<String>> {
+    let mut res = vec![];
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    for c in args.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.get_or_insert_with(String::new).push(c),
+            None if c == '\'' || c == '"' => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => res.extend(current.take()),
+            None => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(anyhow!("Unterminated quote in {}", args));
+    }
+    res.extend(current);
+    Ok(res)
+}
+
+/// The declared parameters, or if there are none, the bind variables of the query as strings
+fn parameters(query: &StandardQuery, dialect: Dialect) -> Vec<QueryParam> {
+    if !query.params.is_empty() {
+        return query.params.clone();
+    }
+    let mut params: Vec<QueryParam> = vec![];
+    for t in tokenize(&query.query, dialect) {
+        if let Some(name) = bind_name(t.kind, t.text) {
+            if !params.iter().any(|p| p.name == name) {
+                params.push(QueryParam {
+                    name: name.to_string(),
+                    type_: ParamType::String,
+                    default: None,
+                });
+            }
+        }
+    }
+    params
+}
+
+fn bind_name(kind: TokenKind, text: &str) -> Option<&str> {
+    match kind {
+        TokenKind::BindVariable => text.strip_prefix(':'),
+        _ => None,
+    }
+}
+
+/// Replaces the `:name` bind variables of the query by the arguments as SQL literals
+fn bind_arguments(query: &StandardQuery, args: &[String], dialect: Dialect) -> Result<String> {
+    let params = parameters(query, dialect);
+    let mut values: BTreeMap<&str, &str> = BTreeMap::new();
+    let mut positional = vec![];
+    for a in args {
+        match a.split_once('=') {
+            Some((n, v)) if params.iter().any(|p| p.name == n) => {
+                if values.insert(n, v).is_some() {
+                    return Err(anyhow!("Argument {} given twice for @{}", n, query.name));
+                }
+            }
+            _ => positional.push(a.as_str()),
+        }
+    }
+    // Positional arguments go to the parameters not given by name, in order
+    let unnamed: Vec<&str> = params
+        .iter()
+        .map(|p| p.name.as_str())
+        .filter(|n| !values.contains_key(n))
+        .collect();
+    if positional.len() > unnamed.len() {
+        return Err(anyhow!("Too many arguments for @{}", query.name));
+    }
+    values.extend(unnamed.into_iter().zip(positional));
+
+    let mut literals = BTreeMap::new();
+    for p in &params {
+        let value = match values.get(p.name.as_str()) {
+            Some(v) => *v,
+            None => p
+                .default
+                .as_deref()
+                .ok_or_else(|| anyhow!("Missing argument {} for @{}", p.name, query.name))?,
+        };
+        literals.insert(p.name.as_str(), literal(p, value, dialect)?);
+    }
+
+    let mut sql = String::new();
+    for t in tokenize(&query.query, dialect) {
+        match bind_name(t.kind, t.text).and_then(|n| literals.get(n)) {
+            Some(l) => sql.push_str(l),
+            None => sql.push_str(t.text),
+        }
+    }
+    Ok(sql)
+}
+
+fn literal(param: &QueryParam, value: &str, dialect: Dialect) -> Result<String> {
+    let invalid = |expected: &str| {
+        anyhow!(
+            "Parameter {} expects {}, got {}",
+            param.name,
+            expected,
+            value
+        )
+    };
+    Ok(match param.type_ {
+        ParamType::Int => {
+            value.parse::<i64>().map_err(|_| invalid("an integer"))?;
+            value.to_string()
+        }
+        ParamType::Number => {
+            // NaN and inf parse as well, but are no SQL literals
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .ok_or_else(|| invalid("a number"))?;
+            value.to_string()
+        }
+        ParamType::Date => {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map_err(|_| invalid("a YYYY-MM-DD date"))?;
+            match dialect {
+                Dialect::Sqlite => format!("'{}'", value),
+                _ => format!("DATE '{}'", value),
+            }
+        }
+        ParamType::String => {
+            let mut s = value.replace('\'', "''");
+            if dialect == Dialect::Mysql {
+                s = s.replace('\\', "\\\\");
+            }
+            format!("'{}'", s)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str, type_: ParamType, default: Option<&str>) -> QueryParam {
+        QueryParam {
+            name: name.into(),
+            type_,
+            default: default.map(String::from),
+        }
+    }
+
+    fn query(sql: &str, params: Vec<QueryParam>) -> StandardQuery {
+        StandardQuery {
+            name: "q".into(),
+            query: sql.into(),
+            description: None,
+            params,
+        }
+    }
+
+    fn args(a: &[&str]) -> Vec<String> {
+        a.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn splits_arguments_at_unquoted_whitespace() {
+        assert_eq!(
+            split_arguments(r#" a  'b c' "d'e" x=''  "#).unwrap(),
+            ["a", "b c", "d'e", "x="]
+        );
+        assert!(split_arguments("").unwrap().is_empty());
+        assert!(split_arguments("'open").is_err());
+    }
+
+    #[test]
+    fn binds_positional_and_named_arguments() {
+        let q = query(
+            "select :a, :b from t where x = :a",
+            vec![
+                param("a", ParamType::String, None),
+                param("b", ParamType::Int, Some("7")),
+            ],
+        );
+        let bind = |a: &[&str]| bind_arguments(&q, &args(a), Dialect::Postgres);
+        assert_eq!(bind(&["x"]).unwrap(), "select 'x', 7 from t where x = 'x'");
+        assert_eq!(
+            bind(&["x", "5"]).unwrap(),
+            "select 'x', 5 from t where x = 'x'"
+        );
+        assert_eq!(
+            bind(&["b=5", "x"]).unwrap(),
+            "select 'x', 5 from t where x = 'x'"
+        );
+        // Positional arguments skip the parameters given by name
+        assert_eq!(
+            bind(&["a=x", "5"]).unwrap(),
+            "select 'x', 5 from t where x = 'x'"
+        );
+        // Not a parameter name, so a value containing `=`
+        assert_eq!(
+            bind(&["c=d"]).unwrap(),
+            "select 'c=d', 7 from t where x = 'c=d'"
+        );
+        assert!(bind(&[]).is_err());
+        assert!(bind(&["x", "5", "6"]).is_err());
+        assert!(bind(&["a=x", "a=y"]).is_err());
+        assert!(bind(&["x", "five"]).is_err());
+    }
+
+    #[test]
+    fn binds_undeclared_variables_as_strings() {
+        let q = query("select :who, ':no' -- :nor\n", vec![]);
+        assert_eq!(
+            bind_arguments(&q, &args(&["me"]), Dialect::Sqlite).unwrap(),
+            "select 'me', ':no' -- :nor\n"
+        );
+    }
+
+    #[test]
+    fn writes_literals() {
+        let lit = |t, v, d| literal(&param("p", t, None), v, d);
+        assert_eq!(
+            lit(ParamType::String, "it's", Dialect::Postgres).unwrap(),
+            "'it''s'"
+        );
+        assert_eq!(
+            lit(ParamType::String, r"a\b", Dialect::Postgres).unwrap(),
+            r"'a\b'"
+        );
+        assert_eq!(
+            lit(ParamType::String, r"a\'", Dialect::Mysql).unwrap(),
+            r"'a\\'''"
+        );
+        assert_eq!(lit(ParamType::Int, "-3", Dialect::Oracle).unwrap(), "-3");
+        assert!(lit(ParamType::Int, "1.5", Dialect::Oracle).is_err());
+        assert_eq!(
+            lit(ParamType::Number, "1.5e3", Dialect::Oracle).unwrap(),
+            "1.5e3"
+        );
+        for v in ["NaN", "inf", "-infinity", "1 or 1=1"] {
+            assert!(lit(ParamType::Number, v, Dialect::Oracle).is_err(), "{}", v);
+        }
+        assert_eq!(
+            lit(ParamType::Date, "2024-01-31", Dialect::Oracle).unwrap(),
+            "DATE '2024-01-31'"
+        );
+        assert_eq!(
+            lit(ParamType::Date, "2024-01-31", Dialect::Sqlite).unwrap(),
+            "'2024-01-31'"
+        );
+        assert!(lit(ParamType::Date, "2024-02-30", Dialect::Oracle).is_err());
+    }
+}
@@ -1,94 +1,343 @@
-use crate::database::Connection;
-use crate::ui::DbcClient;
+use crate::database::{Connection, Error, Outcome, QueryResult};
+use crate::sql::classify::strip_terminator;
+use crate::sql::{classify, split_statements, StatementKind};
+use crate::ui::interrupt::CancelGuard;
+use crate::ui::layout::{fit, terminal_width, text_width};
+use crate::ui::pager;
+use crate::ui::render::write_document;
+use crate::ui::theme::{print_table, Role};
+use crate::ui::{DbcClient, ExecutionSummary, Expanded};
 use anyhow::Result;
-use colored::Colorize;
-use prettytable::format;
-use prettytable::{color, Attr, Cell, Row, Table};
+use prettytable::{Cell, Row, Table};
+use std::io::stdout;
+use std::time::{Duration, Instant};
 
+/// Columns are not wrapped narrower to fit the table into the terminal
+const MIN_WRAP_WIDTH: usize = 10;
+
+/// Runs each statement of the input and prints everything it returns
 pub fn execute_query_and_print_results(
     client: &mut DbcClient,
     conn: &mut Box<dyn Connection>,
     query: &str,
     row_limit: usize,
 ) -> Result<()> {
-    let query = query.trim().trim_end_matches(";");
-    if query.starts_with("select") || query.starts_with("SELECT") {
-        client.set_last_select(&query);
-        let col_limit = client.options.column_limit;
-        let mut more_rows = false;
+    let mut summary = ExecutionSummary::default();
+    for statement in split_statements(query, conn.dialect()) {
+        if !execute_statement(client, conn, statement, row_limit, &mut summary)? {
+            break;
+        }
+    }
+    client.last_execution = Some(summary);
+    Ok(())
+}
 
-        match conn.query(&query) {
-            Ok(res) => {
-                if row_limit == 1 || res.rows.len() == 1 {
-                    let mut table = Table::new();
-                    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
-                    let row = res.rows.iter().nth(0).unwrap();
-                    for (pos, e) in row.data.iter().enumerate() {
-                        table.add_row(Row::new(vec![
-                            Cell::new(&res.columns.iter().nth(pos).unwrap().name)
-                                .with_style(Attr::Bold)
-                                .with_style(Attr::ForegroundColor(color::GREEN)),
-                            match e {
-                                Some(v) => Cell::new(v),
-                                None => Cell::new("NULL")
-                                    .with_style(Attr::ForegroundColor(color::MAGENTA)),
-                            },
-                        ]));
+/// Returns false if the statement failed or was not confirmed, which skips the remaining ones
+fn execute_statement(
+    client: &mut DbcClient,
+    conn: &mut Box<dyn Connection>,
+    query: &str,
+    row_limit: usize,
+    summary: &mut ExecutionSummary,
+) -> Result<bool> {
+    let dialect = conn.dialect();
+    let query = strip_terminator(query, dialect);
+    let classification = classify(query, dialect);
+    summary.session_changed |= classification.kind != StatementKind::Query;
+    if classification.returns_rows && !classification.violates_read_only() {
+        client.set_last_select(query);
+    } else if !super::safeguard::check_statement(client, &classification)? {
+        println!("{}", Role::Warning.paint("Statement not executed."));
+        summary.error = Some("Statement not executed".into());
+        return Ok(false);
+    }
+
+    let start = Instant::now();
+    let result = {
+        let _guard = CancelGuard::new(conn.cancel_handle());
+        conn.run(query)
+    };
+    let elapsed = start.elapsed();
+    summary.duration += elapsed;
+    let ok = match result {
+        Ok(outcomes) => {
+            let mut fetch_time = Duration::default();
+            let mut rows = 0;
+            for outcome in outcomes {
+                match outcome {
+                    Outcome::Rows {
+                        result,
+                        fetch_time: f,
+                    } => {
+                        fetch_time += f;
+                        rows += result.rows.len() as u64;
+                        print_result(client, &result, row_limit)?
                     }
-                    table.printstd();
-                } else {
-                    let mut table = Table::new();
-                    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+                    Outcome::Updated(n) => {
+                        rows += n;
+                        println!("{}", Role::Feedback.paint(format!("{} rows updated.", n)))
+                    }
+                    Outcome::Message(m) => println!("{}", Role::Feedback.paint(m)),
+                }
+            }
+            if client.options.timing {
+                println!(
+                    "{}",
+                    Role::Info.paint(format!(
+                        "Execution {:.3?}, fetch {:.3?}, {} rows.",
+                        elapsed.saturating_sub(fetch_time),
+                        fetch_time,
+                        rows
+                    ))
+                );
+            }
+            summary.rows += rows;
+            summary.ddl |= classification.kind == StatementKind::Ddl;
+            true
+        }
+        Err(e) => {
+            print_error(query, &e);
+            summary.error = Some(format!("{:#}", e));
+            // Errors abort transactions in Postgres
+            summary.session_changed = true;
+            false
+        }
+    };
+    print_messages(conn)?;
+    Ok(ok)
+}
 
-                    let more_columns = res.columns.len() > col_limit;
+/// Prints an error; for server errors with the failing position marked in the statement
+fn print_error(query: &str, e: &anyhow::Error) {
+    match e.downcast_ref::<Error>() {
+        Some(Error::Server {
+            code,
+            message,
+            detail,
+            hint,
+            constraint,
+            position,
+        }) => {
+            println!(
+                "{} {}: {}",
+                Role::Error.paint("Error"),
+                Role::Error.paint(code),
+                message
+            );
+            if let Some(p) = position {
+                print_position(query, *p);
+            }
+            if let Some(d) = detail {
+                println!("{} {}", Role::Warning.paint("Detail:"), d);
+            }
+            if let Some(h) = hint {
+                println!("{} {}", Role::Warning.paint("Hint:"), h);
+            }
+            if let Some(c) = constraint {
+                println!("{} {}", Role::Warning.paint("Constraint:"), c);
+            }
+        }
+        _ => println!("{}: {:#}", Role::Error.paint("Error"), e),
+    }
+}
 
-                    table.set_titles(Row::new(
-                        res.columns
-                            .iter()
-                            .take(col_limit)
-                            .map(|s| {
-                                Cell::new(&s.name)
-                                    .with_style(Attr::Bold)
-                                    .with_style(Attr::ForegroundColor(color::GREEN))
-                            })
-                            .collect(),
-                    ));
+/// Prints the line of the statement containing the character position with a caret below
+fn print_position(query: &str, position: usize) {
+    let mut offset = 0;
+    for line in query.split('\n') {
+        let len = line.chars().count();
+        if position <= offset + len {
+            // Keeps tabs, so that the caret lines up
+            let indent: String = line
+                .chars()
+                .take(position - offset)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            println!("{}", line);
+            println!("{}{}", indent, Role::Mark.paint("^"));
+            return;
+        }
+        offset += len + 1;
+    }
+}
 
-                    let mut c = 0;
-                    for r in res.rows {
-                        table.add_row(Row::new(
-                            r.data
-                                .iter()
-                                .take(col_limit)
-                                .map(|s| match s {
-                                    Some(v) => Cell::new(v),
-                                    None => Cell::new("NULL")
-                                        .with_style(Attr::ForegroundColor(color::MAGENTA)),
-                                })
-                                .collect(),
-                        ));
-                        c = c + 1;
-                        if c >= row_limit {
-                            more_rows = true;
-                            break;
-                        }
-                    }
+/// Prints the notices and warnings the server sent
+fn print_messages(conn: &mut Box<dyn Connection>) -> Result<()> {
+    for m in conn.take_messages()? {
+        println!("{}", Role::Detail.paint(m));
+    }
+    Ok(())
+}
 
-                    table.printstd();
+pub(crate) fn print_result(client: &DbcClient, res: &QueryResult, row_limit: usize) -> Result<()> {
+    let o = &client.options;
+    let rows = &res.rows[..res.rows.len().min(row_limit)];
+    if !o.format.is_table() {
+        write_document(o.format, res, rows, &o.null_string, &mut stdout())?;
+        // On stderr, so that the document stays valid for other programs
+        if res.rows.len() > rows.len() {
+            eprintln!("{}", Role::Warning.paint("More rows exist."));
+        }
+        return Ok(());
+    }
+    if res.rows.is_empty() {
+        println!("{}", Role::Warning.paint("No rows."));
+        return Ok(());
+    }
 
-                    match (more_rows, more_columns) {
-                        (true, true) => println!("{}", "More rows and columns exist.".yellow()),
-                        (true, false) => println!("{}", "More rows exist.".yellow()),
-                        (false, true) => println!("{}", "More columns exist.".yellow()),
-                        _ => (),
-                    }
+    let more_rows = res.rows.len() > rows.len();
+    let terminal = terminal_width();
+    let cell = |v: &Option<String>, width: Option<usize>| match v {
+        Some(v) => Cell::new(&fit(v, width, o.wrap)),
+        None => Role::Null.cell(&o.null_string),
+    };
+    let lines = |v: &Option<String>, width: Option<usize>| match v {
+        Some(v) => fit(v, width, o.wrap).lines().count().max(1),
+        None => 1,
+    };
+    // Output taller than the screen goes to the pager instead
+    let paged = |height: usize| -> Result<bool> {
+        let paged = pager::page(client, res, rows, height)?;
+        if paged && more_rows {
+            println!("{}", Role::Warning.paint("More rows exist."));
+        }
+        Ok(paged)
+    };
+
+    // The width of each column with the values fitted to `max_width`
+    let widths: Vec<usize> = res
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            rows.iter()
+                .map(|r| match &r.data[i] {
+                    Some(v) => text_width(&fit(v, o.max_width, o.wrap)),
+                    None => text_width(&o.null_string),
+                })
+                .chain(std::iter::once(text_width(&c.name)))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let vertical = match o.expanded {
+        Expanded::On => true,
+        Expanded::Off => false,
+        Expanded::Auto => {
+            row_limit == 1
+                || res.rows.len() == 1
+                || terminal
+                    .map(|t| o.format.table_width(&widths) > t)
+                    .unwrap_or(false)
+        }
+    };
+
+    if vertical {
+        let name_width = res.columns.iter().map(|c| text_width(&c.name)).max();
+        // Values get the rest of the terminal
+        let width = match (terminal, name_width) {
+            (Some(t), Some(n)) => {
+                let rest = t.saturating_sub(o.format.table_width(&[n, 0]));
+                Some(o.max_width.map(|m| m.min(rest)).unwrap_or(rest))
+            }
+            _ => o.max_width,
+        };
+        let heading = if rows.len() > 1 { 1 } else { 0 };
+        let height = rows
+            .iter()
+            .map(|r| heading + 2 + r.data.iter().map(|v| lines(v, width)).sum::<usize>())
+            .sum();
+        if paged(height)? {
+            return Ok(());
+        }
+        for (n, r) in rows.iter().enumerate() {
+            if rows.len() > 1 {
+                println!("{}", Role::Heading.paint(format!("-[ RECORD {} ]-", n + 1)));
+            }
+            let mut table = Table::new();
+            table.set_format(o.format.table_format());
+            for (c, v) in res.columns.iter().zip(&r.data) {
+                table.add_row(Row::new(vec![Role::Title.cell(&c.name), cell(v, width)]));
+            }
+            print_table(&table);
+        }
+        if more_rows {
+            println!("{}", Role::Warning.paint("More rows exist."));
+        }
+        return Ok(());
+    }
+
+    // Wraps the widest columns until the table fits into the terminal, then
+    // shows as many columns as fit, at least one
+    let mut widths = widths;
+    let mut limits = vec![o.max_width; widths.len()];
+    if let (Some(t), true) = (terminal, o.wrap) {
+        while o.format.table_width(&widths) > t {
+            let widest = (0..widths.len())
+                .filter(|&i| widths[i] > MIN_WRAP_WIDTH.max(text_width(&res.columns[i].name)))
+                .max_by_key(|&i| widths[i]);
+            match widest {
+                Some(i) => {
+                    widths[i] -= 1;
+                    limits[i] = Some(widths[i]);
                 }
+                None => break,
             }
-            Err(e) => println!("{}: {:?}", "Error".red(), e),
         }
-    } else {
-        let rows_updated = conn.execute(&query)?;
-        println!("{}", format!("{} rows updated.", rows_updated).magenta());
-    };
+    }
+    let mut shown = 0;
+    while shown < widths.len()
+        && (o.column_limit == 0 || shown < o.column_limit)
+        && (shown == 0
+            || terminal
+                .map(|t| o.format.table_width(&widths[..=shown]) <= t)
+                .unwrap_or(true))
+    {
+        shown += 1;
+    }
+    let more_columns = shown < res.columns.len();
+    let height = 4 + rows
+        .iter()
+        .map(|r| {
+            r.data
+                .iter()
+                .zip(&limits)
+                .take(shown)
+                .map(|(v, &width)| lines(v, width))
+                .max()
+                .unwrap_or(1)
+        })
+        .sum::<usize>();
+    if paged(height)? {
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_format(o.format.table_format());
+    table.set_titles(Row::new(
+        res.columns
+            .iter()
+            .take(shown)
+            .map(|c| Role::Title.cell(&c.name))
+            .collect(),
+    ));
+    for r in rows {
+        table.add_row(Row::new(
+            r.data
+                .iter()
+                .zip(&limits)
+                .take(shown)
+                .map(|(v, &width)| cell(v, width))
+                .collect(),
+        ));
+    }
+    print_table(&table);
+
+    match (more_rows, more_columns) {
+        (true, true) => println!("{}", Role::Warning.paint("More rows and columns exist.")),
+        (true, false) => println!("{}", Role::Warning.paint("More rows exist.")),
+        (false, true) => println!("{}", Role::Warning.paint("More columns exist.")),
+        _ => (),
+    }
     Ok(())
 }
@@ -0,0 +1,96 @@
+use crate::sql::Classification;
+use crate::ui::interrupt::interrupted;
+use crate::ui::theme::Role;
+use crate::ui::DbcClient;
+use anyhow::anyhow;
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use std::io::{stdin, stdout, IsTerminal, Write};
+
+/// Why a statement needs confirmation, if it does
+pub fn dangerous_reason(classification: &Classification) -> Option<&'static str> {
+    match classification.keyword.as_str() {
+        "DROP" => Some("DROP"),
+        "TRUNCATE" => Some("TRUNCATE"),
+        "UPDATE" if !classification.has_where => Some("UPDATE without WHERE"),
+        "DELETE" if !classification.has_where => Some("DELETE without WHERE"),
+        _ => None,
+    }
+}
+
+/// Asks a yes/no question on the terminal, defaulting to no; Ctrl-C and Esc mean no
+pub fn confirm(question: &str) -> Result<bool> {
+    interrupted();
+    print!("{} [y/N] ", Role::Warning.paint(question));
+    stdout().flush()?;
+    let answer = if stdin().is_terminal() {
+        read_answer()?
+    } else {
+        let mut answer = String::new();
+        stdin().read_line(&mut answer)?;
+        Some(answer).filter(|_| !interrupted())
+    };
+    Ok(matches!(
+        answer.as_deref().map(str::trim),
+        Some("y" | "Y" | "yes")
+    ))
+}
+
+/// Reads a line in raw mode, where Ctrl-C is a key rather than a signal that
+/// would only be noticed after Enter; `None` if cancelled
+fn read_answer() -> Result<Option<String>> {
+    terminal::enable_raw_mode()?;
+    let answer = read_keys();
+    let _ = terminal::disable_raw_mode();
+    println!();
+    answer
+}
+
+fn read_keys() -> Result<Option<String>> {
+    let mut answer = String::new();
+    loop {
+        if let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) = event::read()?
+        {
+            match code {
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Enter => return Ok(Some(answer)),
+                KeyCode::Backspace if !answer.is_empty() => {
+                    answer.pop();
+                    print!("\u{8} \u{8}");
+                }
+                KeyCode::Char(c) => {
+                    answer.push(c);
+                    print!("{}", c);
+                }
+                _ => {}
+            }
+            stdout().flush()?;
+        }
+    }
+}
+
+/// Checks a statement against the read-only and confirmation settings
+///
+/// Returns an error for statements rejected on read-only connections and
+/// `false` if the user declined to execute a dangerous statement.
+pub fn check_statement(client: &DbcClient, classification: &Classification) -> Result<bool> {
+    if client.options.read_only && classification.violates_read_only() {
+        return Err(anyhow!(
+            "Connection is read-only, refusing to execute {}",
+            classification.keyword
+        ));
+    }
+    if client.options.confirm_writes {
+        if let Some(reason) = dangerous_reason(classification) {
+            return confirm(&format!("Really execute {}?", reason));
+        }
+    }
+    Ok(true)
+}
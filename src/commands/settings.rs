@@ -0,0 +1,279 @@
+use crate::config::ClientSettings;
+use crate::database::Connection;
+use crate::ui::theme::Role;
+use crate::ui::DbcClient;
+use anyhow::anyhow;
+use anyhow::Result;
+use serde_yaml::{Mapping, Value};
+use std::time::Duration;
+
+/// Applies client settings from the config file. Settings the connection does not
+/// support only print a warning, so that a global `settings:` block does not keep
+/// other databases from connecting.
+pub fn apply_settings(
+    client: &mut DbcClient,
+    conn: &mut Box<dyn Connection>,
+    settings: &ClientSettings,
+) -> Result<()> {
+    apply(client, conn, settings, false)
+}
+
+/// Applies the settings that only change how results are shown, without a connection
+pub fn apply_display_settings(client: &mut DbcClient, settings: &ClientSettings) -> Result<()> {
+    if let Some(n) = settings.row_limit {
+        client.options.set_row_limit(n);
+    }
+    if let Some(n) = settings.column_limit {
+        client.options.set_column_limit(n);
+    }
+    if let Some(f) = settings.format {
+        client.options.format = f;
+    }
+    if let Some(e) = settings.expanded {
+        client.options.expanded = e;
+    }
+    if let Some(w) = settings.max_width {
+        client.options.max_width = if w == 0 { None } else { Some(w) };
+    }
+    if let Some(w) = settings.wrap {
+        client.options.wrap = w;
+    }
+    if let Some(p) = settings.pager {
+        client.options.pager = p;
+    }
+    if let Some(s) = &settings.null_string {
+        client.options.null_string = s.clone();
+    }
+    if let Some(t) = &settings.theme {
+        crate::ui::theme::use_theme(t)?;
+        client.options.theme = t.clone();
+    }
+    if let Some(t) = settings.timing {
+        client.options.timing = t;
+    }
+    Ok(())
+}
+
+/// Applies the settings; with `strict`, settings the connection rejects are errors
+fn apply(
+    client: &mut DbcClient,
+    conn: &mut Box<dyn Connection>,
+    settings: &ClientSettings,
+    strict: bool,
+) -> Result<()> {
+    apply_display_settings(client, settings)?;
+    if let Some(c) = &settings.prompt_color {
+        client.options.prompt_color = Some(
+            c.parse()
+                .map_err(|_| anyhow!("Unknown prompt color {}", c))?,
+        );
+    }
+    if let Some(p) = &settings.prompt {
+        crate::ui::prompt::validate(p)?;
+        if crate::ui::prompt::uses_session(p) {
+            client.session = conn.session_info()?;
+        }
+        client.options.prompt = Some(p.clone());
+    }
+    apply_connection_settings(client, conn, settings, strict)?;
+    if let Some(cw) = settings.confirm_writes {
+        client.options.confirm_writes = cw;
+    }
+    if let Some(t) = &settings.schema_ttl {
+        client.options.schema_ttl = parse_duration(t)?;
+    }
+    if let Some(statements) = &settings.startup_sql {
+        let limit = client.options.row_limit;
+        for s in statements {
+            super::query::execute_query_and_print_results(client, conn, s, limit)?;
+        }
+    }
+    Ok(())
+}
+
+/// Applies the settings kept by the connection itself, without running `startup_sql`
+pub(crate) fn apply_connection_settings(
+    client: &mut DbcClient,
+    conn: &mut Box<dyn Connection>,
+    settings: &ClientSettings,
+    strict: bool,
+) -> Result<()> {
+    let supported = |name: &str, res: Result<()>| -> Result<bool> {
+        match res {
+            Ok(()) => Ok(true),
+            Err(e) if !strict => {
+                eprintln!(
+                    "{}",
+                    Role::Warning.paint(format!("Warning: {} not applied: {:#}", name, e))
+                );
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    };
+    if let Some(ro) = settings.read_only {
+        // Statements are still checked by dbc if the connection cannot enforce it
+        supported("read_only", conn.set_read_only(ro))?;
+        client.options.read_only = ro;
+    }
+    if let Some(ac) = settings.autocommit {
+        if supported("autocommit", conn.set_autocommit(ac))? {
+            client.options.autocommit = ac;
+        }
+    }
+    if let Some(tz) = &settings.timezone {
+        if supported("timezone", conn.set_time_zone(tz))? {
+            client.options.timezone = Some(tz.clone());
+        }
+    }
+    if let Some(so) = settings.serveroutput {
+        if supported("serveroutput", conn.set_server_output(so))? {
+            client.options.server_output = so;
+        }
+    }
+    if let Some(t) = &settings.timeout {
+        let timeout = parse_duration(t)?;
+        if supported("timeout", conn.set_timeout(timeout))? {
+            client.options.timeout = timeout;
+        }
+    }
+    Ok(())
+}
+
+/// Parses durations like `30s`, `500ms`, `5m`, `1h` or plain seconds; `0` and `off` mean none
+pub(crate) fn parse_duration(s: &str) -> Result<Option<Duration>> {
+    let s = s.trim();
+    if s == "0" || s == "off" {
+        return Ok(None);
+    }
+    let (n, unit) = s.split_at(
+        s.find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(s.len()),
+    );
+    let n: f64 = n.parse().map_err(|_| anyhow!("Invalid duration {}", s))?;
+    let secs = match unit.trim() {
+        "" | "s" => n,
+        "ms" => n / 1000.0,
+        "m" | "min" => n * 60.0,
+        "h" => n * 3600.0,
+        u => return Err(anyhow!("Unknown unit {} in duration {}", u, s)),
+    };
+    Duration::try_from_secs_f64(secs)
+        .map(Some)
+        .map_err(|_| anyhow!("Invalid duration {}", s))
+}
+
+/// Handles `:set <name> <value>`
+pub fn execute_set(
+    client: &mut DbcClient,
+    conn: &mut Box<dyn Connection>,
+    name: &str,
+    value: &str,
+) -> Result<()> {
+    if name == "startup_sql" {
+        return Err(anyhow!("startup_sql can only be given in the config file"));
+    }
+
+    // The value is read as YAML scalar first (numbers, booleans), then on/off
+    // as boolean, then as plain string
+    let as_settings = |v: Value| -> Option<ClientSettings> {
+        let mut m = Mapping::new();
+        m.insert(Value::String(name.into()), v);
+        serde_yaml::from_value(Value::Mapping(m)).ok()
+    };
+    let settings = serde_yaml::from_str(value)
+        .ok()
+        .and_then(as_settings)
+        .or_else(|| match value {
+            "on" => as_settings(Value::Bool(true)),
+            "off" => as_settings(Value::Bool(false)),
+            _ => None,
+        })
+        .or_else(|| as_settings(Value::String(value.into())))
+        .ok_or_else(|| anyhow!("Unknown setting {} or invalid value {}", name, value))?;
+
+    apply(client, conn, &settings, true)
+}
+
+/// Prints the current settings, for `:set` without arguments
+pub fn print_settings(client: &DbcClient) {
+    let o = &client.options;
+    let settings: Vec<(&str, String)> = vec![
+        ("row_limit", o.row_limit.to_string()),
+        ("column_limit", o.column_limit.to_string()),
+        ("format", format!("{:?}", o.format).to_lowercase()),
+        ("expanded", format!("{:?}", o.expanded).to_lowercase()),
+        (
+            "max_width",
+            o.max_width
+                .map(|w| w.to_string())
+                .unwrap_or_else(|| "off".into()),
+        ),
+        ("wrap", o.wrap.to_string()),
+        ("pager", format!("{:?}", o.pager).to_lowercase()),
+        ("autocommit", o.autocommit.to_string()),
+        ("read_only", o.read_only.to_string()),
+        ("confirm_writes", o.confirm_writes.to_string()),
+        ("timezone", o.timezone.clone().unwrap_or_default()),
+        ("null_string", o.null_string.clone()),
+        (
+            "prompt_color",
+            o.prompt_color
+                .map(|c| format!("{:?}", c).to_lowercase())
+                .unwrap_or_default(),
+        ),
+        ("theme", o.theme.clone()),
+        ("prompt", o.prompt.clone().unwrap_or_default()),
+        ("serveroutput", o.server_output.to_string()),
+        (
+            "timeout",
+            o.timeout
+                .map(|t| format!("{:?}", t))
+                .unwrap_or_else(|| "off".into()),
+        ),
+        ("timing", o.timing.to_string()),
+        (
+            "schema_ttl",
+            o.schema_ttl
+                .map(|t| format!("{:?}", t))
+                .unwrap_or_else(|| "off".into()),
+        ),
+    ];
+    for (k, v) in settings {
+        println!("{} {}", Role::Title.paint(format!("{:14}", k)), v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        let d = |s| parse_duration(s).unwrap();
+        assert_eq!(d("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(d(" 30 "), Some(Duration::from_secs(30)));
+        assert_eq!(d("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(d("1.5m"), Some(Duration::from_secs(90)));
+        assert_eq!(d("5 min"), Some(Duration::from_secs(300)));
+        assert_eq!(d("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(d("0"), None);
+        assert_eq!(d("off"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for s in [
+            "",
+            "s",
+            "-5s",
+            "1..2s",
+            "10d",
+            "nan",
+            "1e30",
+            "9".repeat(400).as_str(),
+        ] {
+            assert!(parse_duration(s).is_err(), "{:?}", s);
+        }
+    }
+}
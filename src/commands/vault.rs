@@ -0,0 +1,39 @@
+use crate::config::vault::Vault;
+use crate::ui::theme::Role;
+use crate::ui::VaultCommand;
+use anyhow::Result;
+
+pub fn execute_vault_command(cmd: VaultCommand) -> Result<()> {
+    let mut vault = Vault::open()?;
+    match cmd {
+        VaultCommand::Set { name } => {
+            let password = rpassword::prompt_password(format!("Password for {}: ", name))?;
+            vault.set(&name, &password)?;
+            vault.save()?;
+            println!(
+                "{}",
+                Role::Success.paint(format!("Stored password {}.", name))
+            );
+        }
+        VaultCommand::Remove { name } => {
+            if vault.remove(&name) {
+                vault.save()?;
+                println!(
+                    "{}",
+                    Role::Success.paint(format!("Removed password {}.", name))
+                );
+            } else {
+                println!(
+                    "{}",
+                    Role::Warning.paint(format!("No password {} in the vault.", name))
+                );
+            }
+        }
+        VaultCommand::List => {
+            for name in vault.names() {
+                println!("{}", name);
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,16 +1,628 @@
-use super::database::ConnectionParams;
+use super::database::{ConnectionParams, Dialect, StandardQuery};
+use super::ui::theme::ThemeDefinition;
+use super::ui::{Expanded, OutputFormat, Pager};
+use anyhow::anyhow;
+use anyhow::Context;
 use anyhow::Result;
-use dirs::home_dir;
-use std::collections::HashMap;
+use dirs::{config_dir, home_dir};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fmt::Display;
 use std::fs::File;
+use std::path::{Path, PathBuf};
 
-pub fn read_config() -> Result<HashMap<String, ConnectionParams>> {
-    let f = home_dir().unwrap().join(".dbc.yml");
-    if f.exists() {
-        let file = File::open(f)?;
-        let res: HashMap<String, ConnectionParams> = serde_yaml::from_reader(file)?;
-        Ok(res)
-    } else {
-        Ok(HashMap::new())
+pub mod queries;
+pub mod secrets;
+pub mod vault;
+
+/// Environment variable pointing to a config file that replaces the global ones
+pub const CONFIG_ENV: &str = "DBC_CONFIG";
+
+/// Name of the project-local config files searched from the working directory upwards
+pub const LOCAL_CONFIG_NAME: &str = ".dbc.yml";
+
+/// Where a single configuration value was defined
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin {
+    pub file: PathBuf,
+    /// The connection the value was inherited from via `extends`
+    pub inherited_from: Option<String>,
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(i) = &self.inherited_from {
+            write!(f, " (extends {})", i)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct RawConnection {
+    values: Mapping,
+    origins: BTreeMap<String, Origin>,
+}
+
+/// The merged configuration of all config files
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// The config files that were read, in the order they were merged
+    pub files: Vec<PathBuf>,
+    pub connections: BTreeMap<String, ConnectionParams>,
+    /// Global client settings from the top-level `settings:` block
+    pub settings: ClientSettings,
+    /// Client settings from the `settings:` block of each connection
+    pub connection_settings: BTreeMap<String, ClientSettings>,
+    /// Queries from the top-level `queries:` block
+    pub queries: BTreeMap<String, StandardQuery>,
+    /// Queries from the `queries:` block of each connection
+    pub connection_queries: BTreeMap<String, BTreeMap<String, StandardQuery>>,
+    /// Themes from the top-level `themes:` block
+    pub themes: BTreeMap<String, ThemeDefinition>,
+    /// Lists of identifiers from the top-level `groups:` block, for `:fanout`
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// Connections that could not be resolved, with the reason
+    pub invalid: BTreeMap<String, String>,
+    /// Resolved (but not yet interpolated) raw values per connection, for reporting
+    pub values: BTreeMap<String, Mapping>,
+    pub origins: BTreeMap<String, BTreeMap<String, Origin>>,
+}
+
+impl Config {
+    /// Returns the parameters of a connection, or why there are none
+    pub fn connection(&self, identifier: &str) -> Result<ConnectionParams> {
+        if let Some(e) = self.invalid.get(identifier) {
+            return Err(anyhow!("Invalid configuration of {}: {}", identifier, e));
+        }
+        self.connections
+            .get(identifier)
+            .cloned()
+            .ok_or_else(|| anyhow!("No such identifier {}", identifier))
+    }
+
+    /// The global settings, overridden by those of the connection
+    pub fn settings_for(&self, identifier: &str) -> ClientSettings {
+        match self.connection_settings.get(identifier) {
+            Some(s) => self.settings.clone().merge(s.clone()),
+            None => self.settings.clone(),
+        }
+    }
+
+    /// The user's queries for a connection: those of the query directory,
+    /// overridden by the global ones and those of the connection
+    pub fn queries_for(&self, identifier: &str, dialect: Dialect) -> Result<Vec<StandardQuery>> {
+        let mut res: BTreeMap<String, StandardQuery> = queries::read_query_dir(dialect)?
+            .into_iter()
+            .map(|q| (q.name.clone(), q))
+            .collect();
+        res.extend(self.queries.clone());
+        if let Some(q) = self.connection_queries.get(identifier) {
+            res.extend(q.clone());
+        }
+        Ok(res.into_values().collect())
+    }
+
+    /// The identifiers of a group: those listed under its name in the `groups:`
+    /// block, otherwise the connections tagged with its name
+    pub fn group(&self, name: &str) -> Result<Vec<String>> {
+        let members: Vec<String> = match self.groups.get(name) {
+            Some(g) => g.clone(),
+            None => self
+                .connections
+                .iter()
+                .filter(|(_, p)| p.has_tag(name))
+                .map(|(i, _)| i.clone())
+                .collect(),
+        };
+        if members.is_empty() {
+            return Err(anyhow!("No group or tag {}", name));
+        }
+        Ok(members)
+    }
+}
+
+/// Client settings, given globally or per connection in a `settings:` block
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientSettings {
+    pub row_limit: Option<usize>,
+    pub column_limit: Option<usize>,
+    pub format: Option<OutputFormat>,
+    /// `on`, `off` or `auto` for vertical output of tables wider than the terminal
+    pub expanded: Option<Expanded>,
+    /// Width of values before they are wrapped or cut, 0 for no limit
+    pub max_width: Option<usize>,
+    /// Wrap long values instead of cutting them
+    pub wrap: Option<bool>,
+    /// `auto`, `external` or `off` for results that do not fit on the screen
+    pub pager: Option<Pager>,
+    pub autocommit: Option<bool>,
+    pub read_only: Option<bool>,
+    pub confirm_writes: Option<bool>,
+    pub timezone: Option<String>,
+    pub null_string: Option<String>,
+    pub prompt_color: Option<String>,
+    /// A built-in theme or one of the `themes:` block
+    pub theme: Option<String>,
+    /// Template of the prompt, like `{user}@{host}/{schema}{tx}> `
+    pub prompt: Option<String>,
+    /// Print Oracle DBMS_OUTPUT lines after each statement
+    pub serveroutput: Option<bool>,
+    /// Maximum runtime of statements, like `30s`, `500ms` or `5m`
+    #[serde(default, deserialize_with = "duration_text")]
+    pub timeout: Option<String>,
+    /// Print execution and fetch time after each statement
+    pub timing: Option<bool>,
+    /// How long the schema cache on disk is used, like `12h`; `0` reloads it on every start
+    #[serde(default, deserialize_with = "duration_text")]
+    pub schema_ttl: Option<String>,
+    /// Statements executed right after connecting
+    pub startup_sql: Option<Vec<String>>,
+}
+
+impl ClientSettings {
+    /// Combines two sets of settings, the values of `other` win
+    pub fn merge(self, other: ClientSettings) -> ClientSettings {
+        ClientSettings {
+            row_limit: other.row_limit.or(self.row_limit),
+            column_limit: other.column_limit.or(self.column_limit),
+            format: other.format.or(self.format),
+            expanded: other.expanded.or(self.expanded),
+            max_width: other.max_width.or(self.max_width),
+            wrap: other.wrap.or(self.wrap),
+            pager: other.pager.or(self.pager),
+            autocommit: other.autocommit.or(self.autocommit),
+            read_only: other.read_only.or(self.read_only),
+            confirm_writes: other.confirm_writes.or(self.confirm_writes),
+            timezone: other.timezone.or(self.timezone),
+            null_string: other.null_string.or(self.null_string),
+            prompt_color: other.prompt_color.or(self.prompt_color),
+            theme: other.theme.or(self.theme),
+            prompt: other.prompt.or(self.prompt),
+            serveroutput: other.serveroutput.or(self.serveroutput),
+            timeout: other.timeout.or(self.timeout),
+            timing: other.timing.or(self.timing),
+            schema_ttl: other.schema_ttl.or(self.schema_ttl),
+            startup_sql: other.startup_sql.or(self.startup_sql),
+        }
+    }
+}
+
+/// A duration like `30s`, or a number of seconds
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DurationText {
+    Text(String),
+    Seconds(f64),
+}
+
+fn duration_text<'de, D: Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    Ok(Option::<DurationText>::deserialize(d)?.map(|t| match t {
+        DurationText::Text(s) => s,
+        DurationText::Seconds(n) => n.to_string(),
+    }))
+}
+
+/// The config files in the order they are merged: global files first, then
+/// project-local ones from the outermost directory to the working directory
+pub fn config_files() -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    match env::var_os(CONFIG_ENV) {
+        Some(f) => files.push(PathBuf::from(f)),
+        None => {
+            if let Some(d) = config_dir() {
+                files.push(d.join("dbc").join("config.yml"));
+            }
+            if let Some(h) = home_dir() {
+                files.push(h.join(LOCAL_CONFIG_NAME));
+            }
+        }
+    }
+
+    // `~/.dbc.yml` is a global file, also when the working directory is below home
+    let home_config = home_dir().map(|h| h.join(LOCAL_CONFIG_NAME));
+    let mut local = vec![];
+    let mut dir = env::current_dir().ok();
+    while let Some(d) = dir {
+        let f = d.join(LOCAL_CONFIG_NAME);
+        if !files.contains(&f) && home_config.as_ref() != Some(&f) {
+            local.push(f);
+        }
+        dir = d.parent().map(|p| p.to_path_buf());
+    }
+    local.reverse();
+    files.extend(local);
+
+    Ok(files.into_iter().filter(|f| f.exists()).collect())
+}
+
+#[derive(Default)]
+struct RawConfig {
+    connections: BTreeMap<String, RawConnection>,
+    settings: Mapping,
+    queries: Mapping,
+    themes: Mapping,
+    groups: Mapping,
+    files: Vec<PathBuf>,
+    visited: HashSet<PathBuf>,
+}
+
+pub fn read_config() -> Result<Config> {
+    let mut raw = RawConfig::default();
+    for f in config_files()? {
+        merge_file(&f, &mut raw)?;
+    }
+
+    let mut config = Config {
+        files: raw.files,
+        settings: interpolate_mapping(&raw.settings)
+            .and_then(|m| Ok(serde_yaml::from_value(Value::Mapping(m))?))
+            .context("Invalid global settings")?,
+        queries: queries::parse_queries(&raw.queries).context("Invalid global queries")?,
+        themes: serde_yaml::from_value(Value::Mapping(raw.themes)).context("Invalid themes")?,
+        groups: serde_yaml::from_value(Value::Mapping(raw.groups)).context("Invalid groups")?,
+        ..Config::default()
+    };
+    for identifier in raw.connections.keys() {
+        match resolve_extends(identifier, &raw.connections, &mut vec![]) {
+            Ok(r) => {
+                match parse_connection(&r.values) {
+                    Ok((p, settings, queries)) => {
+                        config.connections.insert(identifier.clone(), p);
+                        config
+                            .connection_settings
+                            .insert(identifier.clone(), settings);
+                        config
+                            .connection_queries
+                            .insert(identifier.clone(), queries);
+                    }
+                    Err(e) => {
+                        config
+                            .invalid
+                            .insert(identifier.clone(), format!("{:#}", e));
+                    }
+                }
+                config.values.insert(identifier.clone(), r.values);
+                config.origins.insert(identifier.clone(), r.origins);
+            }
+            Err(e) => {
+                config
+                    .invalid
+                    .insert(identifier.clone(), format!("{:#}", e));
+            }
+        }
+    }
+
+    Ok(config)
+}
+
+type ParsedConnection = (
+    ConnectionParams,
+    ClientSettings,
+    BTreeMap<String, StandardQuery>,
+);
+
+fn parse_connection(values: &Mapping) -> Result<ParsedConnection> {
+    // Queries are not interpolated, `${...}` may well be part of their SQL
+    let mut values = values.clone();
+    let queries = match values.remove("queries") {
+        Some(Value::Mapping(q)) => queries::parse_queries(&q).context("Invalid queries")?,
+        Some(_) => return Err(anyhow!("queries must be a mapping")),
+        None => BTreeMap::new(),
+    };
+    let mut m = interpolate_mapping(&values)?;
+    let mut settings: ClientSettings = match m.remove("settings") {
+        Some(s) => serde_yaml::from_value(s).context("Invalid settings")?,
+        None => ClientSettings::default(),
+    };
+    let params: ConnectionParams = serde_yaml::from_value(Value::Mapping(m))?;
+    settings.read_only = settings.read_only.or(params.read_only);
+    Ok((params, settings, queries))
+}
+
+fn merge_file(path: &Path, raw: &mut RawConfig) -> Result<()> {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if !raw.visited.insert(canonical) {
+        return Ok(());
+    }
+    let file = File::open(path).with_context(|| format!("Cannot open config file {:?}", path))?;
+    let doc: Value = serde_yaml::from_reader(file)
+        .with_context(|| format!("Cannot parse config file {:?}", path))?;
+    let doc = match doc {
+        Value::Mapping(m) => m,
+        Value::Null => Mapping::new(),
+        _ => return Err(anyhow!("Config file {:?} is not a mapping", path)),
+    };
+
+    // Included files come first, so that the including file can override them
+    if let Some(include) = doc.get("include") {
+        let includes: Vec<String> = serde_yaml::from_value(match include {
+            Value::String(_) => Value::Sequence(vec![include.clone()]),
+            v => v.clone(),
+        })
+        .with_context(|| format!("include in {:?} must be a file or a list of files", path))?;
+        for i in includes {
+            let i = interpolate(&i)?;
+            let p = expand_path(&i, path.parent());
+            merge_file(&p, raw)
+                .with_context(|| format!("Cannot include {:?} from {:?}", p, path))?;
+        }
+    }
+
+    raw.files.push(path.to_path_buf());
+    for (k, v) in doc {
+        let identifier = match k {
+            Value::String(s) => s,
+            k => serde_yaml::to_string(&k)?.trim().to_string(),
+        };
+        if identifier == "include" {
+            continue;
+        }
+        let values = match v {
+            Value::Mapping(m) => m,
+            _ => return Err(anyhow!("{} in {:?} is not a mapping", identifier, path)),
+        };
+        if identifier == "settings" {
+            merge_mapping(&mut raw.settings, values);
+            continue;
+        }
+        if identifier == "queries" {
+            merge_mapping(&mut raw.queries, values);
+            continue;
+        }
+        if identifier == "themes" {
+            merge_mapping(&mut raw.themes, values);
+            continue;
+        }
+        if identifier == "groups" {
+            merge_mapping(&mut raw.groups, values);
+            continue;
+        }
+        let entry = raw.connections.entry(identifier).or_default();
+        for (k, v) in values {
+            if let Value::String(key) = &k {
+                entry.origins.insert(
+                    key.clone(),
+                    Origin {
+                        file: path.to_path_buf(),
+                        inherited_from: None,
+                    },
+                );
+            }
+            merge_value(&mut entry.values, k, v);
+        }
+    }
+    Ok(())
+}
+
+/// Inserts a value, merging nested mappings key by key instead of replacing them
+fn merge_value(target: &mut Mapping, k: Value, v: Value) {
+    match (target.get_mut(&k), v) {
+        (Some(Value::Mapping(t)), Value::Mapping(v)) => merge_mapping(t, v),
+        (_, v) => {
+            target.insert(k, v);
+        }
+    }
+}
+
+fn merge_mapping(target: &mut Mapping, values: Mapping) {
+    for (k, v) in values {
+        merge_value(target, k, v);
+    }
+}
+
+/// Resolves the `extends` chain of a connection; its own values win over inherited ones
+fn resolve_extends(
+    identifier: &str,
+    raw: &BTreeMap<String, RawConnection>,
+    chain: &mut Vec<String>,
+) -> Result<RawConnection> {
+    if chain.iter().any(|c| c == identifier) {
+        chain.push(identifier.to_string());
+        return Err(anyhow!("Cyclic extends: {}", chain.join(" -> ")));
+    }
+    chain.push(identifier.to_string());
+
+    let own = raw
+        .get(identifier)
+        .ok_or_else(|| anyhow!("extends unknown connection {}", identifier))?;
+    let mut res = match own.values.get("extends") {
+        Some(Value::String(base)) => {
+            let mut r = resolve_extends(base, raw, chain)?;
+            for o in r.origins.values_mut() {
+                o.inherited_from.get_or_insert_with(|| base.clone());
+            }
+            r
+        }
+        Some(_) => return Err(anyhow!("extends must name a single connection")),
+        None => RawConnection::default(),
+    };
+    merge_mapping(&mut res.values, own.values.clone());
+    for (k, o) in &own.origins {
+        res.origins.insert(k.clone(), o.clone());
+    }
+    res.values.remove("extends");
+    res.origins.remove("extends");
+    Ok(res)
+}
+
+fn interpolate_mapping(m: &Mapping) -> Result<Mapping> {
+    let mut res = Mapping::new();
+    for (k, v) in m {
+        let v = interpolate_value(v).with_context(|| match k {
+            Value::String(k) => format!("in {}", k),
+            _ => "in config".to_string(),
+        })?;
+        res.insert(k.clone(), v);
+    }
+    Ok(res)
+}
+
+fn interpolate_value(v: &Value) -> Result<Value> {
+    Ok(match v {
+        Value::String(s) => Value::String(interpolate(s)?),
+        Value::Sequence(s) => {
+            Value::Sequence(s.iter().map(interpolate_value).collect::<Result<_>>()?)
+        }
+        Value::Mapping(m) => Value::Mapping(interpolate_mapping(m)?),
+        v => v.clone(),
+    })
+}
+
+/// Replaces `${VAR}` and `${VAR:-default}` with the value of the environment variable
+pub fn interpolate(s: &str) -> Result<String> {
+    let re = Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)(?::-([^}]*))?\}").unwrap();
+    let mut missing = None;
+    let res = re.replace_all(s, |c: &Captures| match env::var(&c[1]) {
+        Ok(v) => v,
+        Err(_) => match c.get(2) {
+            Some(d) => d.as_str().to_string(),
+            None => {
+                missing.get_or_insert_with(|| c[1].to_string());
+                String::new()
+            }
+        },
+    });
+    match missing {
+        Some(v) => Err(anyhow!("Environment variable {} is not set", v)),
+        None => Ok(res.into_owned()),
+    }
+}
+
+fn expand_path(p: &str, relative_to: Option<&Path>) -> PathBuf {
+    if let Some(rest) = p.strip_prefix("~/") {
+        if let Some(h) = home_dir() {
+            return h.join(rest);
+        }
+    }
+    let path = PathBuf::from(p);
+    match relative_to {
+        Some(d) if path.is_relative() => d.join(path),
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(entries: &[(&str, &str)]) -> BTreeMap<String, RawConnection> {
+        entries
+            .iter()
+            .map(|(identifier, yaml)| {
+                let values: Mapping = serde_yaml::from_str(yaml).unwrap();
+                let origins = values
+                    .keys()
+                    .filter_map(|k| k.as_str())
+                    .map(|k| {
+                        let origin = Origin {
+                            file: PathBuf::from(format!("{}.yml", identifier)),
+                            inherited_from: None,
+                        };
+                        (k.to_string(), origin)
+                    })
+                    .collect();
+                (identifier.to_string(), RawConnection { values, origins })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn interpolates_variables_and_defaults() {
+        env::set_var("DBC_TEST_INTERPOLATE_HOST", "db.example.com");
+        env::remove_var("DBC_TEST_INTERPOLATE_UNSET");
+        assert_eq!(
+            interpolate("//${DBC_TEST_INTERPOLATE_HOST}:${DBC_TEST_INTERPOLATE_UNSET:-5432}/app")
+                .unwrap(),
+            "//db.example.com:5432/app"
+        );
+        assert_eq!(interpolate("${DBC_TEST_INTERPOLATE_UNSET:-}").unwrap(), "");
+        assert_eq!(
+            interpolate("$HOME and ${ not a variable").unwrap(),
+            "$HOME and ${ not a variable"
+        );
+    }
+
+    #[test]
+    fn fails_on_unset_variables() {
+        env::remove_var("DBC_TEST_INTERPOLATE_MISSING");
+        let e = interpolate("pw ${DBC_TEST_INTERPOLATE_MISSING}").unwrap_err();
+        assert!(e.to_string().contains("DBC_TEST_INTERPOLATE_MISSING"));
+    }
+
+    #[test]
+    fn interpolates_nested_values() {
+        env::set_var("DBC_TEST_INTERPOLATE_SCHEMA", "app");
+        let m: Mapping =
+            serde_yaml::from_str("settings:\n  startup_sql: [\"SET search_path = ${DBC_TEST_INTERPOLATE_SCHEMA}\"]\nport: 5432").unwrap();
+        let m = interpolate_mapping(&m).unwrap();
+        let expected: Mapping = serde_yaml::from_str(
+            "settings:\n  startup_sql: [\"SET search_path = app\"]\nport: 5432",
+        )
+        .unwrap();
+        assert_eq!(m, expected);
+    }
+
+    #[test]
+    fn read_only_on_the_connection_is_a_setting() {
+        let m: Mapping = serde_yaml::from_str("type: pg\nread_only: true").unwrap();
+        let (_, settings, _) = parse_connection(&m).unwrap();
+        assert_eq!(settings.read_only, Some(true));
+        let m: Mapping =
+            serde_yaml::from_str("type: pg\nread_only: true\nsettings: {read_only: false}")
+                .unwrap();
+        let (_, settings, _) = parse_connection(&m).unwrap();
+        assert_eq!(settings.read_only, Some(false));
+    }
+
+    #[test]
+    fn durations_can_be_numbers() {
+        let s: ClientSettings = serde_yaml::from_str("timeout: 30\nschema_ttl: 1.5").unwrap();
+        assert_eq!(s.timeout.as_deref(), Some("30"));
+        assert_eq!(s.schema_ttl.as_deref(), Some("1.5"));
+        let s: ClientSettings = serde_yaml::from_str("timeout: 500ms").unwrap();
+        assert_eq!(s.timeout.as_deref(), Some("500ms"));
+        assert!(serde_yaml::from_str::<ClientSettings>("timeout: [1]").is_err());
+    }
+
+    #[test]
+    fn extends_merges_inherited_values() {
+        let raw = raw(&[
+            (
+                "base",
+                "type: pg\nusername: app\nsettings: {row_limit: 10, timing: true}",
+            ),
+            (
+                "prod",
+                "extends: base\nurl: //prod/app\nsettings: {row_limit: 50}",
+            ),
+        ]);
+        let r = resolve_extends("prod", &raw, &mut vec![]).unwrap();
+        let expected: Mapping = serde_yaml::from_str(
+            "type: pg\nusername: app\nsettings: {row_limit: 50, timing: true}\nurl: //prod/app",
+        )
+        .unwrap();
+        assert_eq!(r.values, expected);
+        assert_eq!(r.origins["type"].inherited_from.as_deref(), Some("base"));
+        assert_eq!(r.origins["url"].inherited_from, None);
+        assert!(!r.values.contains_key("extends"));
+    }
+
+    #[test]
+    fn extends_detects_cycles_and_unknown_connections() {
+        let raw = raw(&[
+            ("a", "extends: b"),
+            ("b", "extends: a"),
+            ("c", "extends: d"),
+        ]);
+        let e = resolve_extends("a", &raw, &mut vec![]).unwrap_err();
+        assert_eq!(e.to_string(), "Cyclic extends: a -> b -> a");
+        let e = resolve_extends("c", &raw, &mut vec![]).unwrap_err();
+        assert_eq!(e.to_string(), "extends unknown connection d");
     }
 }
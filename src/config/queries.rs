@@ -0,0 +1,266 @@
+use crate::database::{Dialect, ParamType, QueryParam, StandardQuery};
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use dirs::home_dir;
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// A query of a `queries:` block, either just the SQL or with description and parameters
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum QueryDefinition {
+    Sql(String),
+    Full {
+        query: String,
+        description: Option<String>,
+        #[serde(default)]
+        params: Vec<ParamDefinition>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ParamDefinition {
+    Name(String),
+    Full {
+        name: String,
+        #[serde(rename = "type", default)]
+        type_: ParamType,
+        default: Option<Value>,
+    },
+}
+
+/// The directory holding the `.sql` files of a dialect, `~/.dbc/queries/<dialect>`
+pub fn query_dir(dialect: Dialect) -> Option<PathBuf> {
+    let name = match dialect {
+        Dialect::Postgres => "pg",
+        Dialect::Oracle => "ora",
+        Dialect::Mysql => "mysql",
+        Dialect::Sqlite => "sqlite",
+    };
+    home_dir().map(|h| h.join(".dbc").join("queries").join(name))
+}
+
+/// Reads the queries of a `queries:` block
+pub fn parse_queries(m: &Mapping) -> Result<BTreeMap<String, StandardQuery>> {
+    let mut queries = BTreeMap::new();
+    for (k, v) in m {
+        let name = k
+            .as_str()
+            .ok_or_else(|| anyhow!("Query names must be strings"))?;
+        let def: QueryDefinition = serde_yaml::from_value(v.clone()).with_context(|| {
+            format!(
+                "Invalid query {}, expected SQL or query/description/params",
+                name
+            )
+        })?;
+        let query = match def {
+            QueryDefinition::Sql(query) => StandardQuery {
+                name: name.into(),
+                query,
+                ..Default::default()
+            },
+            QueryDefinition::Full {
+                query,
+                description,
+                params,
+            } => StandardQuery {
+                name: name.into(),
+                query,
+                description,
+                params: params
+                    .into_iter()
+                    .map(|p| param(p).with_context(|| format!("in query {}", name)))
+                    .collect::<Result<_>>()?,
+            },
+        };
+        queries.insert(name.to_string(), query);
+    }
+    Ok(queries)
+}
+
+fn param(def: ParamDefinition) -> Result<QueryParam> {
+    Ok(match def {
+        ParamDefinition::Name(name) => QueryParam {
+            name,
+            type_: ParamType::default(),
+            default: None,
+        },
+        ParamDefinition::Full {
+            name,
+            type_,
+            default,
+        } => QueryParam {
+            default: match default {
+                None | Some(Value::Null) => None,
+                Some(Value::String(s)) => Some(s),
+                Some(Value::Number(n)) => Some(n.to_string()),
+                Some(Value::Bool(b)) => Some(b.to_string()),
+                Some(_) => return Err(anyhow!("The default of {} must be a scalar", name)),
+            },
+            name,
+            type_,
+        },
+    })
+}
+
+/// Reads the `.sql` files of the query directory of a dialect, if it exists
+pub fn read_query_dir(dialect: Dialect) -> Result<Vec<StandardQuery>> {
+    let dir = match query_dir(dialect) {
+        Some(d) if d.is_dir() => d,
+        _ => return Ok(vec![]),
+    };
+    let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+        .with_context(|| format!("Cannot read query directory {:?}", dir))?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().map(|e| e == "sql").unwrap_or(false))
+        .collect();
+    files.sort();
+
+    let mut queries = vec![];
+    for f in files {
+        let name = match f.file_stem().and_then(|s| s.to_str()) {
+            Some(n) => n.to_string(),
+            None => continue,
+        };
+        let text =
+            fs::read_to_string(&f).with_context(|| format!("Cannot read query file {:?}", f))?;
+        queries.push(parse_sql_file(&name, &text).with_context(|| format!("in {:?}", f))?);
+    }
+    Ok(queries)
+}
+
+/// Reads a query file; its leading comment is the description, except for
+/// lines like `-- @param customer int 42` declaring a parameter
+fn parse_sql_file(name: &str, text: &str) -> Result<StandardQuery> {
+    let mut description = vec![];
+    let mut params = vec![];
+    for line in text.lines().map(str::trim) {
+        let comment = match line.strip_prefix("--") {
+            Some(c) => c.trim(),
+            None if line.is_empty() => continue,
+            None => break,
+        };
+        let param = comment
+            .strip_prefix("@param")
+            .filter(|p| p.is_empty() || p.starts_with(char::is_whitespace));
+        match param {
+            Some(p) => {
+                let mut parts = p.split_whitespace();
+                let name = parts
+                    .next()
+                    .ok_or_else(|| anyhow!("@param needs a name"))?
+                    .to_string();
+                let type_ = match parts.next() {
+                    Some(t) => serde_yaml::from_value(Value::String(t.into()))
+                        .map_err(|_| anyhow!("Unknown type {} of parameter {}", t, name))?,
+                    None => ParamType::default(),
+                };
+                let default = parts.collect::<Vec<_>>().join(" ");
+                params.push(QueryParam {
+                    name,
+                    type_,
+                    default: Some(default).filter(|d| !d.is_empty()),
+                });
+            }
+            None if !comment.is_empty() => description.push(comment),
+            None => (),
+        }
+    }
+    Ok(StandardQuery {
+        name: name.into(),
+        query: text.trim().to_string(),
+        description: Some(description.join(" ")).filter(|d| !d.is_empty()),
+        params,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(yaml: &str) -> Result<BTreeMap<String, StandardQuery>> {
+        parse_queries(&serde_yaml::from_str(yaml).unwrap())
+    }
+
+    #[test]
+    fn parses_short_and_full_queries() {
+        let q = parse(
+            "
+hello: select :who
+orders:
+  description: Orders of a customer
+  query: select * from orders where customer_id = :customer and created > :since
+  params:
+    - name: customer
+      type: int
+      default: 42
+    - since
+",
+        )
+        .unwrap();
+        assert_eq!(q["hello"].query, "select :who");
+        assert!(q["hello"].params.is_empty());
+        let o = &q["orders"];
+        assert_eq!(o.description.as_deref(), Some("Orders of a customer"));
+        assert_eq!(
+            o.params,
+            [
+                QueryParam {
+                    name: "customer".into(),
+                    type_: ParamType::Int,
+                    default: Some("42".into()),
+                },
+                QueryParam {
+                    name: "since".into(),
+                    type_: ParamType::String,
+                    default: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        assert!(parse("q: [1, 2]").is_err());
+        assert!(parse("q:\n  query: x\n  params:\n    - name: a\n      default: [1]").is_err());
+        assert!(parse("q:\n  query: x\n  params:\n    - name: a\n      type: blob").is_err());
+    }
+
+    #[test]
+    fn reads_description_and_params_of_sql_files() {
+        let q = parse_sql_file(
+            "orders",
+            "-- Latest orders\n--   of a customer\n-- @param customer int\n\
+             -- @param since date 2024-01-01\n-- @param note\n\n\
+             select * from orders -- not the description\n",
+        )
+        .unwrap();
+        assert_eq!(q.name, "orders");
+        assert_eq!(
+            q.description.as_deref(),
+            Some("Latest orders of a customer")
+        );
+        assert_eq!(
+            q.params
+                .iter()
+                .map(|p| (p.name.as_str(), p.type_, p.default.as_deref()))
+                .collect::<Vec<_>>(),
+            [
+                ("customer", ParamType::Int, None),
+                ("since", ParamType::Date, Some("2024-01-01")),
+                ("note", ParamType::String, None),
+            ]
+        );
+        assert!(q.query.ends_with("-- not the description"));
+
+        let q = parse_sql_file("plain", "select 1").unwrap();
+        assert_eq!(q.description, None);
+        assert!(parse_sql_file("bad", "-- @param x blob\nselect 1").is_err());
+        assert!(parse_sql_file("bad", "-- @param \nselect 1").is_err());
+    }
+}
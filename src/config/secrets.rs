@@ -0,0 +1,182 @@
+use super::vault::Vault;
+use crate::database::ConnectionParams;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use regex::Regex;
+use std::env;
+use std::fs;
+use std::process::Command;
+
+/// Determines the password of a connection from the configured source
+///
+/// The sources are tried in this order: plaintext `password`, `password_env`,
+/// `password_command`, `password_file` (in `.pgpass` format), `password_vault`.
+/// If none of them is configured, the password is prompted for interactively.
+pub fn resolve_password(identifier: &str, params: &ConnectionParams) -> Result<String> {
+    if let Some(p) = &params.password {
+        return Ok(p.clone());
+    }
+    if let Some(var) = &params.password_env {
+        return env::var(var)
+            .with_context(|| format!("Environment variable {} for the password is not set", var));
+    }
+    if let Some(cmd) = &params.password_command {
+        return password_from_command(cmd);
+    }
+    if let Some(f) = &params.password_file {
+        return password_from_pgpass(f, params)?
+            .ok_or_else(|| anyhow!("No matching entry for {} in {}", identifier, f));
+    }
+    if let Some(name) = &params.password_vault {
+        let vault = Vault::open()?;
+        return vault
+            .get(name)?
+            .ok_or_else(|| anyhow!("No entry {} in the vault", name));
+    }
+    Ok(rpassword::prompt_password(format!(
+        "Password for {}: ",
+        identifier
+    ))?)
+}
+
+fn password_from_command(cmd: &str) -> Result<String> {
+    let output = if cfg!(windows) {
+        Command::new("cmd").args(["/C", cmd]).output()
+    } else {
+        Command::new("sh").args(["-c", cmd]).output()
+    }
+    .with_context(|| format!("Cannot run password command {}", cmd))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Password command {} failed: {}",
+            cmd,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    // Like `pass`, only the first line is the password
+    let stdout = String::from_utf8(output.stdout)?;
+    Ok(stdout.lines().next().unwrap_or("").to_string())
+}
+
+/// Looks up the password in a file with lines of the form
+/// `hostname:port:database:username:password`, where `*` matches anything
+fn password_from_pgpass(file: &str, params: &ConnectionParams) -> Result<Option<String>> {
+    let content =
+        fs::read_to_string(file).with_context(|| format!("Cannot read password file {}", file))?;
+    Ok(find_in_pgpass(&content, params))
+}
+
+/// The password of the first line matching the connection
+fn find_in_pgpass(content: &str, params: &ConnectionParams) -> Option<String> {
+    let re = Regex::new(r"//([^/:]+)(?::(\d+))?(?:/(\w+))?").unwrap();
+    let url = params.url.clone().unwrap_or_default();
+    let c = re.captures(&url);
+    let host = c.as_ref().and_then(|c| c.get(1)).map(|m| m.as_str());
+    let default_port = match params.type_.as_deref().unwrap_or("ora") {
+        "pg" | "postgresql" => Some("5432"),
+        "mysql" => Some("3306"),
+        "ora" | "oracle" => Some("1521"),
+        _ => None,
+    };
+    let port = c
+        .as_ref()
+        .and_then(|c| c.get(2))
+        .map(|m| m.as_str())
+        .or(default_port);
+    let dbname = params
+        .dbname
+        .as_deref()
+        .or_else(|| c.as_ref().and_then(|c| c.get(3)).map(|m| m.as_str()));
+    let wanted = [host, port, dbname, params.username.as_deref()];
+
+    for line in content.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = split_pgpass_line(line);
+        if fields.len() != 5 {
+            continue;
+        }
+        let matches = wanted
+            .iter()
+            .zip(fields.iter())
+            .all(|(w, f)| f == "*" || w.map(|w| w == f).unwrap_or(false));
+        if matches {
+            return Some(fields[4].clone());
+        }
+    }
+    None
+}
+
+/// Splits a `.pgpass` line at unescaped colons, resolving `\:` and `\\`
+fn split_pgpass_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(n) = chars.next() {
+                    fields.last_mut().unwrap().push(n);
+                }
+            }
+            ':' => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(type_: &str, url: &str, username: &str) -> ConnectionParams {
+        ConnectionParams {
+            type_: Some(type_.into()),
+            url: Some(url.into()),
+            username: Some(username.into()),
+            ..ConnectionParams::default()
+        }
+    }
+
+    #[test]
+    fn splits_at_unescaped_colons() {
+        assert_eq!(
+            split_pgpass_line(r"db:5432:app:me:se\:cr\\et"),
+            vec!["db", "5432", "app", "me", r"se:cr\et"]
+        );
+        assert_eq!(split_pgpass_line("a::b"), vec!["a", "", "b"]);
+    }
+
+    #[test]
+    fn matches_fields_and_wildcards() {
+        let content = "\
+# comment
+other:5432:app:me:wrong
+db.example.com:5432:app:you:wrong
+db.example.com:*:app:me:right
+*:*:*:*:fallback
+";
+        let p = params("pg", "//db.example.com:5432/app", "me");
+        assert_eq!(find_in_pgpass(content, &p), Some("right".into()));
+        let p = params("pg", "//elsewhere:5432/app", "me");
+        assert_eq!(find_in_pgpass(content, &p), Some("fallback".into()));
+    }
+
+    #[test]
+    fn uses_default_port_and_dbname() {
+        let content = "db:3306:shop:me:secret\n";
+        let mut p = params("mysql", "//db/other", "me");
+        assert_eq!(find_in_pgpass(content, &p), None);
+        p.dbname = Some("shop".into());
+        assert_eq!(find_in_pgpass(content, &p), Some("secret".into()));
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        let p = params("pg", "//db:5432/app", "me");
+        assert_eq!(find_in_pgpass("db:5432:app:me\n", &p), None);
+    }
+}
@@ -0,0 +1,149 @@
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use dirs::home_dir;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::path::PathBuf;
+
+/// Environment variable holding the master passphrase, if it should not be prompted for
+pub const PASSPHRASE_ENV: &str = "DBC_VAULT_PASSPHRASE";
+
+/// Environment variable pointing to an alternative vault file
+pub const VAULT_ENV: &str = "DBC_VAULT";
+
+const CHECK_VALUE: &str = "dbc-vault";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultFile {
+    salt: String,
+    /// Known plaintext encrypted with the vault key, used to reject wrong passphrases early
+    check: Option<Entry>,
+    entries: BTreeMap<String, Entry>,
+}
+
+/// An encrypted store for passwords, unlocked with a master passphrase
+///
+/// The file itself only contains the salt and one ChaCha20-Poly1305
+/// encrypted blob per entry, so it can be checked into a dotfile repository.
+pub struct Vault {
+    path: PathBuf,
+    file: VaultFile,
+    cipher: ChaCha20Poly1305,
+}
+
+impl Vault {
+    pub fn default_path() -> PathBuf {
+        match env::var_os(VAULT_ENV) {
+            Some(p) => PathBuf::from(p),
+            None => home_dir().unwrap().join(".dbc_vault.yml"),
+        }
+    }
+
+    /// Opens the vault at the default location, creating an empty one if it does not exist yet
+    pub fn open() -> Result<Self> {
+        let path = Self::default_path();
+        let file = if path.exists() {
+            let f = File::open(&path).with_context(|| format!("Cannot open vault {:?}", path))?;
+            serde_yaml::from_reader(f)?
+        } else {
+            let mut salt = [0u8; 16];
+            rand::thread_rng().fill_bytes(&mut salt);
+            VaultFile {
+                salt: BASE64.encode(salt),
+                check: None,
+                entries: BTreeMap::new(),
+            }
+        };
+
+        let passphrase = match env::var(PASSPHRASE_ENV) {
+            Ok(p) => p,
+            Err(_) => rpassword::prompt_password("Vault passphrase: ")?,
+        };
+        let salt = BASE64.decode(&file.salt)?;
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| anyhow!("Cannot derive vault key: {}", e))?;
+
+        let mut vault = Self {
+            path,
+            file,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+        };
+        match &vault.file.check {
+            Some(e) => {
+                if vault.decrypt(e).ok().as_deref() != Some(CHECK_VALUE) {
+                    return Err(anyhow!("Wrong vault passphrase"));
+                }
+            }
+            None => vault.file.check = Some(vault.encrypt(CHECK_VALUE)?),
+        }
+        Ok(vault)
+    }
+
+    fn encrypt(&self, secret: &str) -> Result<Entry> {
+        let mut nonce = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), secret.as_bytes())
+            .map_err(|_| anyhow!("Cannot encrypt vault entry"))?;
+        Ok(Entry {
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        })
+    }
+
+    fn decrypt(&self, e: &Entry) -> Result<String> {
+        let nonce = BASE64.decode(&e.nonce)?;
+        let ciphertext = BASE64.decode(&e.ciphertext)?;
+        let plain = self
+            .cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| anyhow!("Cannot decrypt vault entry"))?;
+        Ok(String::from_utf8(plain)?)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.file.entries.keys().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        match self.file.entries.get(name) {
+            Some(e) => Ok(Some(self.decrypt(e)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set(&mut self, name: &str, secret: &str) -> Result<()> {
+        let e = self.encrypt(secret)?;
+        self.file.entries.insert(name.to_string(), e);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.file.entries.remove(name).is_some()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let f = File::create(&self.path)
+            .with_context(|| format!("Cannot write vault {:?}", self.path))?;
+        serde_yaml::to_writer(f, &self.file)?;
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::anyhow;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use thiserror::Error;
 
 #[cfg(feature = "mysql-support")]
@@ -10,21 +11,107 @@ pub mod ora;
 pub mod pg;
 #[cfg(feature = "sqlite-support")]
 pub mod sqlite;
+pub mod tls;
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("No result found")]
     NoResultError,
+    /// An error reported by the database server for a statement
+    #[error("{code}: {message}")]
+    Server {
+        /// SQLSTATE, ORA- number, MySQL or SQLite error code
+        code: String,
+        message: String,
+        detail: Option<String>,
+        hint: Option<String>,
+        constraint: Option<String>,
+        /// Character offset of the failing position in the statement
+        position: Option<usize>,
+    },
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// How hard a connection insists on TLS, modelled after libpq's `sslmode`
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SslMode {
+    /// Never use TLS
+    #[default]
+    Disable,
+    /// Use TLS if the server supports it, without verifying the certificate
+    Prefer,
+    /// Always use TLS, without verifying the certificate
+    Require,
+    /// Always use TLS and verify the certificate chain, but not the host name
+    VerifyCa,
+    /// Always use TLS and verify both the certificate chain and the host name
+    VerifyFull,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct ConnectionParams {
     #[serde(rename = "type")]
     pub type_: Option<String>,
     pub url: Option<String>,
     pub username: Option<String>,
     pub password: Option<String>,
+    /// Name of an environment variable holding the password
+    pub password_env: Option<String>,
+    /// Command printing the password on its first line of output, e.g. `pass show db/prod`
+    pub password_command: Option<String>,
+    /// File in `.pgpass` format (`hostname:port:database:username:password`)
+    pub password_file: Option<String>,
+    /// Name of the entry in the encrypted password vault
+    pub password_vault: Option<String>,
     pub dbname: Option<String>,
+    /// TLS mode, defaults to `disable`
+    pub sslmode: Option<SslMode>,
+    /// PEM file with the CA certificates used to verify the server
+    pub sslrootcert: Option<String>,
+    /// Client certificate (PEM for Postgres, PKCS#12 archive for MySQL)
+    pub sslcert: Option<String>,
+    /// PEM file with the unencrypted PKCS#8 private key belonging to `sslcert` (Postgres only)
+    pub sslkey: Option<String>,
+    /// Password of the PKCS#12 archive given in `sslcert` (MySQL only)
+    pub sslpassword: Option<String>,
+    /// Overrides whether the server host name is checked against its certificate
+    pub ssl_verify_hostname: Option<bool>,
+    /// Free-form tags; `production` highlights the prompt and asks before dangerous statements
+    pub tags: Option<Vec<String>>,
+    /// Short for `read_only` in the `settings:` block of the connection
+    pub read_only: Option<bool>,
+}
+
+impl ConnectionParams {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().flatten().any(|t| t == tag)
+    }
+
+    /// The dialect of the database type; connections without a type are Oracle
+    pub fn dialect(&self) -> Result<Dialect> {
+        match self.type_.as_deref().unwrap_or("ora") {
+            "pg" | "postgresql" => Ok(Dialect::Postgres),
+            "sqlite" => Ok(Dialect::Sqlite),
+            "ora" | "oracle" => Ok(Dialect::Oracle),
+            "mysql" => Ok(Dialect::Mysql),
+            _ => Err(anyhow!("Unknown database type {:?}", &self.type_)),
+        }
+    }
+
+    pub fn ssl_mode(&self) -> SslMode {
+        self.sslmode.unwrap_or_default()
+    }
+
+    /// Whether the server certificate chain has to be verified
+    pub fn ssl_verify_certificate(&self) -> bool {
+        matches!(self.ssl_mode(), SslMode::VerifyCa | SslMode::VerifyFull)
+    }
+
+    /// Whether the server host name has to match its certificate
+    pub fn ssl_verify_hostname(&self) -> bool {
+        self.ssl_verify_hostname
+            .unwrap_or(self.ssl_mode() == SslMode::VerifyFull)
+    }
 }
 
 pub struct Column {
@@ -40,40 +127,235 @@ pub struct QueryResult {
     pub rows: Vec<Row>,
 }
 
+/// One result of running a statement; procedures and blocks can produce several
+pub enum Outcome {
+    /// A result set, with the time spent fetching its rows after executing
+    Rows {
+        result: QueryResult,
+        fetch_time: Duration,
+    },
+    Updated(u64),
+    Message(String),
+}
+
+/// A node of an execution plan, with the figures the database reports for it
+#[derive(Debug, Default)]
+pub struct PlanNode {
+    pub operation: String,
+    /// Conditions, sort keys and the like
+    pub details: Vec<String>,
+    /// Estimated cost, including the children
+    pub cost: Option<f64>,
+    /// Estimated rows
+    pub rows: Option<f64>,
+    /// Rows returned over all loops, with `:explain analyze`
+    pub actual_rows: Option<f64>,
+    /// Milliseconds spent over all loops including the children, with `:explain analyze`
+    pub actual_time: Option<f64>,
+    pub children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    /// Builds the tree from nodes given as (id, parent id, node), parents before their
+    /// children; several roots get a common parent
+    pub fn from_flat(mut nodes: Vec<(i64, Option<i64>, PlanNode)>) -> PlanNode {
+        let mut roots = vec![];
+        // Children are attached before their parent is moved
+        while let Some((_, parent, node)) = nodes.pop() {
+            match parent.and_then(|p| nodes.iter_mut().find(|(id, _, _)| *id == p)) {
+                Some((_, _, p)) => p.children.insert(0, node),
+                None => roots.insert(0, node),
+            }
+        }
+        if roots.len() == 1 {
+            roots.remove(0)
+        } else {
+            PlanNode {
+                operation: "Plan".into(),
+                children: roots,
+                ..Default::default()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TransactionState {
+    #[default]
+    Idle,
+    Active,
+    /// Postgres rejects everything but ROLLBACK after an error in a transaction
+    Failed,
+}
+
+/// The context of a session, shown in the prompt
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SessionInfo {
+    pub user: String,
+    /// The current schema, or database for MySQL
+    pub schema: String,
+    pub host: String,
+    pub transaction: TransactionState,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TableRef {
     pub schema: String,
     pub name: String,
 }
 
-#[derive(Clone)]
-pub struct StandardQuery<'a> {
-    pub name: &'a str,
-    pub query: &'a str,
+/// A named query run with `@name`, either bundled with dbc or defined by the user
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StandardQuery {
+    pub name: String,
+    pub query: String,
+    pub description: Option<String>,
+    /// Parameters referenced as `:name` in the query, in positional order
+    pub params: Vec<QueryParam>,
+}
+
+impl StandardQuery {
+    pub fn new(name: &str, query: &str, description: &str) -> StandardQuery {
+        StandardQuery {
+            name: name.into(),
+            query: query.into(),
+            description: Some(description.into()),
+            params: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryParam {
+    pub name: String,
+    pub type_: ParamType,
+    pub default: Option<String>,
+}
+
+/// How the value of a query parameter is checked and written into the query
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    #[default]
+    String,
+    Int,
+    Number,
+    Date,
+}
+
+/// The bundled queries, replaced or extended by the user's queries of the same name
+fn merge_queries(builtin: Vec<StandardQuery>, user: &[StandardQuery]) -> Vec<StandardQuery> {
+    let mut queries: Vec<StandardQuery> = builtin
+        .into_iter()
+        .filter(|q| !user.iter().any(|u| u.name == q.name))
+        .collect();
+    queries.extend(user.iter().cloned());
+    queries
+}
+
+/// The SQL dialect spoken by a connection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dialect {
+    Postgres,
+    Oracle,
+    Mysql,
+    Sqlite,
+}
+
+impl Dialect {
+    /// Short name used in prompts and file names
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            Dialect::Postgres => "pg",
+            Dialect::Oracle => "ora",
+            Dialect::Mysql => "my",
+            Dialect::Sqlite => "sqlite",
+        }
+    }
 }
 
+/// Cancels the statement running on a connection; called from another thread
+pub type CancelHandle = Box<dyn Fn() -> Result<()> + Send + Sync>;
+
 pub trait Connection {
+    fn identifier(&self) -> &str;
+
+    fn dialect(&self) -> Dialect;
+
     fn print_connection_info(&mut self) -> Result<()>;
 
     fn execute(&mut self, statement: &str) -> Result<u64>;
 
     fn query(&mut self, statement: &str) -> Result<QueryResult>;
 
+    /// Runs a statement of any kind and returns everything it produced, in order
+    fn run(&mut self, statement: &str) -> Result<Vec<Outcome>>;
+
+    /// The execution plan of a statement; `analyze` executes it to get actual figures
+    fn explain(&mut self, statement: &str, analyze: bool) -> Result<PlanNode>;
+
     fn list_tables(&mut self) -> Result<Vec<TableRef>>;
 
-    fn prompt(&self) -> String;
+    /// The column names of a table; an empty schema means the default one
+    fn list_columns(&mut self, table: &TableRef) -> Result<Vec<String>>;
 
+    /// The bundled queries merged with those set by `set_user_queries`
     fn standard_queries(&self) -> Vec<StandardQuery>;
 
+    /// Sets the queries defined in the config and the query directory
+    fn set_user_queries(&mut self, queries: Vec<StandardQuery>);
+
     fn describe(&mut self, obj: &str) -> Result<()>;
 
     fn search(&mut self, obj: &str) -> Result<()>;
+
+    /// Makes the session read-only (or writable again) on the database side
+    fn set_read_only(&mut self, read_only: bool) -> Result<()>;
+
+    /// Whether statements are committed right away; only Oracle starts without
+    fn autocommit(&self) -> bool {
+        true
+    }
+
+    fn set_autocommit(&mut self, autocommit: bool) -> Result<()>;
+
+    fn set_time_zone(&mut self, time_zone: &str) -> Result<()>;
+
+    /// Limits the runtime of statements, `None` removes the limit
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()>;
+
+    /// Enables collecting Oracle DBMS_OUTPUT lines; other backends always collect their messages
+    fn set_server_output(&mut self, enabled: bool) -> Result<()>;
+
+    /// Notices, warnings and output lines the server sent since the last call
+    fn take_messages(&mut self) -> Result<Vec<String>>;
+
+    /// The current user, schema, server and transaction state
+    fn session_info(&mut self) -> Result<SessionInfo>;
+
+    fn cancel_handle(&self) -> CancelHandle;
+}
+
+/// The parameters with the password looked up, so that they can connect again without asking
+pub fn resolve_params(identifier: &str, params: ConnectionParams) -> Result<ConnectionParams> {
+    if params.type_.as_deref() == Some("sqlite") {
+        return Ok(params);
+    }
+    Ok(ConnectionParams {
+        password: Some(crate::config::secrets::resolve_password(
+            identifier, &params,
+        )?),
+        ..params
+    })
 }
 
 pub fn create_connection(
     identifier: &str,
     params: ConnectionParams,
 ) -> Result<Box<dyn Connection>> {
-    match params.clone().type_.unwrap_or("ora".into()).as_ref() {
+    let type_ = params.clone().type_.unwrap_or("ora".into());
+    let params = resolve_params(identifier, params)?;
+    match type_.as_ref() {
         "pg" | "postgresql" => Ok(Box::new(pg::PgConnection::create(identifier, params)?)),
         #[cfg(feature = "sqlite-support")]
         "sqlite" => Ok(Box::new(sqlite::SqliteConnection::create(
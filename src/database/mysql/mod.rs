@@ -0,0 +1,479 @@
+use super::CancelHandle;
+use super::Connection;
+use super::ConnectionParams;
+use super::Dialect;
+use super::Error;
+use super::SslMode;
+use super::{Column, Outcome, PlanNode, QueryResult, Row};
+use super::{SessionInfo, TransactionState};
+use anyhow::anyhow;
+use anyhow::Result;
+use chrono;
+use chrono::{Local, NaiveDate, TimeZone};
+use mysql::prelude::*;
+use mysql::{ClientIdentity, Opts, OptsBuilder, SslOpts};
+use regex::Regex;
+use std::mem;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+pub struct MysqlConnection {
+    identifier: String,
+    conn: mysql::Conn,
+    /// For the extra connection that cancels statements
+    opts: Opts,
+    /// Warnings of the last statements, from SHOW WARNINGS
+    messages: Vec<String>,
+    _params: ConnectionParams,
+    user_queries: Vec<super::StandardQuery>,
+}
+
+impl MysqlConnection {
+    pub fn create(identifier: &str, params: ConnectionParams) -> Result<Self> {
+        let re = Regex::new(r"//([^/:]+)/(\w+)$").unwrap();
+        let p = params.clone();
+        let u = p.url.expect("PG needs a URL");
+        let c = re
+            .captures(&u)
+            .expect("Format of URL needs to be //host/db");
+        let p = params.clone();
+        let mut b = OptsBuilder::new();
+        b = b
+            .ip_or_hostname(Some(&c[1]))
+            .db_name(Some(&c[2]))
+            .user(p.username)
+            .pass(p.password);
+        if let Some(ssl_opts) = ssl_opts(&params)? {
+            b = b.ssl_opts(ssl_opts);
+        }
+        let opts = Opts::from(b);
+        let conn = mysql::Conn::new(opts.clone())?;
+
+        Ok(Self {
+            identifier: identifier.to_string(),
+            conn,
+            opts,
+            messages: vec![],
+            _params: params,
+            user_queries: vec![],
+        })
+    }
+}
+
+fn ssl_opts(params: &ConnectionParams) -> Result<Option<SslOpts>> {
+    match params.ssl_mode() {
+        SslMode::Disable => Ok(None),
+        SslMode::Prefer => Err(anyhow!(
+            "sslmode prefer is not supported for MySQL, use disable or require"
+        )),
+        _ => {
+            if params.sslkey.is_some() {
+                return Err(anyhow!(
+                    "MySQL expects the client certificate as PKCS#12 archive in sslcert, sslkey is not supported"
+                ));
+            }
+            let identity = params.sslcert.as_ref().map(|f| {
+                let identity = ClientIdentity::new(Path::new(f).to_path_buf());
+                match &params.sslpassword {
+                    Some(pw) => identity.with_password(pw.clone()),
+                    None => identity,
+                }
+            });
+            let mut opts = SslOpts::default()
+                .with_client_identity(identity)
+                .with_danger_accept_invalid_certs(!params.ssl_verify_certificate())
+                .with_danger_skip_domain_validation(!params.ssl_verify_hostname());
+            if let Some(f) = &params.sslrootcert {
+                opts = opts.with_root_cert_path(Some(Path::new(f).to_path_buf()));
+            }
+            Ok(Some(opts))
+        }
+    }
+}
+
+impl Connection for MysqlConnection {
+    fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    fn dialect(&self) -> Dialect {
+        Dialect::Mysql
+    }
+
+    fn print_connection_info(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn execute(&mut self, statement: &str) -> Result<u64> {
+        self.conn.exec_drop(statement, ())?;
+        Ok(self.conn.affected_rows())
+    }
+    fn query(&mut self, statement: &str) -> Result<QueryResult> {
+        let stmt = self.conn.prep(statement)?;
+        let rows: Vec<mysql::Row> = self.conn.exec(&stmt, ())?;
+        let columns: Vec<Column> = stmt
+            .columns()
+            .iter()
+            .map(|c| Column {
+                name: c.name_str().to_string(),
+            })
+            .collect();
+        let data = rows
+            .iter()
+            .map(|r| {
+                let n = r.len();
+
+                Row {
+                    data: (0..n).map(|i| conv(r.get(i))).collect(),
+                }
+            })
+            .collect();
+        Ok(QueryResult {
+            columns,
+            rows: data,
+        })
+    }
+    fn run(&mut self, statement: &str) -> Result<Vec<Outcome>> {
+        let mut outcomes = vec![];
+        let mut warnings = 0;
+        // Uses the text protocol, which allows several result sets, e.g. from CALL
+        let se = |e| server_error(e, statement);
+        let mut result = self.conn.query_iter(statement).map_err(se)?;
+        while let Some(mut set) = result.iter() {
+            let columns: Vec<Column> = set
+                .columns()
+                .as_ref()
+                .iter()
+                .map(|c| Column {
+                    name: c.name_str().to_string(),
+                })
+                .collect();
+            if columns.is_empty() {
+                warnings += set.warnings();
+                outcomes.push(Outcome::Updated(set.affected_rows()));
+                let info = set.info_str();
+                if !info.is_empty() {
+                    outcomes.push(Outcome::Message(info.into_owned()));
+                }
+            } else {
+                let start = Instant::now();
+                let rows = set
+                    .by_ref()
+                    .map(|r| {
+                        r.map(|r| Row {
+                            data: (0..r.len()).map(|i| conv(r.get(i))).collect(),
+                        })
+                    })
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(se)?;
+                warnings += set.warnings();
+                outcomes.push(Outcome::Rows {
+                    result: QueryResult { columns, rows },
+                    fetch_time: start.elapsed(),
+                });
+            }
+        }
+        drop(result);
+        if warnings > 0 {
+            let w: Vec<(String, u32, String)> = self.conn.query("SHOW WARNINGS")?;
+            self.messages.extend(
+                w.into_iter()
+                    .map(|(level, code, message)| format!("{} {}: {}", level, code, message)),
+            );
+        }
+        Ok(outcomes)
+    }
+    fn explain(&mut self, statement: &str, analyze: bool) -> Result<PlanNode> {
+        let se = |e| server_error(e, statement);
+        if analyze {
+            // Only available as text tree
+            let tree: Option<String> = self
+                .conn
+                .query_first(format!("EXPLAIN ANALYZE {}", statement))
+                .map_err(se)?;
+            Ok(tree_plan(&tree.unwrap_or_default()))
+        } else {
+            let json: Option<String> = self
+                .conn
+                .query_first(format!("EXPLAIN FORMAT=JSON {}", statement))
+                .map_err(se)?;
+            let plan: serde_json::Value = serde_json::from_str(&json.unwrap_or_default())?;
+            let mut root = json_plan_node("Query".into(), &plan);
+            Ok(if root.children.len() == 1 {
+                root.children.remove(0)
+            } else {
+                root
+            })
+        }
+    }
+    fn list_tables(&mut self) -> std::result::Result<Vec<super::TableRef>, anyhow::Error> {
+        Ok(self
+            .conn
+            .query_map("show tables", |name: String| super::TableRef {
+                schema: "".into(),
+                name,
+            })?)
+    }
+    fn list_columns(&mut self, table: &super::TableRef) -> Result<Vec<String>> {
+        Ok(self.conn.exec(
+            "select column_name from information_schema.columns
+              where table_name = ? and table_schema = coalesce(nullif(?, ''), database())
+              order by ordinal_position",
+            (&table.name, &table.schema),
+        )?)
+    }
+    fn standard_queries(&self) -> Vec<super::StandardQuery> {
+        let queries = vec![
+            super::StandardQuery::new(
+                "locks",
+                include_str!("query_locks.sql"),
+                "Blocked sessions and the sessions blocking them",
+            ),
+            super::StandardQuery::new(
+                "queries",
+                include_str!("query_queries.sql"),
+                "Statements running right now",
+            ),
+            super::StandardQuery::new(
+                "sizes",
+                include_str!("query_sizes.sql"),
+                "Size of the schemas",
+            ),
+            super::StandardQuery::new(
+                "sessions",
+                include_str!("query_sessions.sql"),
+                "Connected sessions",
+            ),
+            super::StandardQuery::new(
+                "processlist",
+                include_str!("query_processlist.sql"),
+                "SHOW FULL PROCESSLIST",
+            ),
+            super::StandardQuery::new(
+                "innodb_status",
+                include_str!("query_innodb_status.sql"),
+                "SHOW ENGINE INNODB STATUS",
+            ),
+        ];
+        super::merge_queries(queries, &self.user_queries)
+    }
+    fn set_user_queries(&mut self, queries: Vec<super::StandardQuery>) {
+        self.user_queries = queries;
+    }
+    fn describe(&mut self, _: &str) -> Result<()> {
+        todo!()
+    }
+    fn search(&mut self, _: &str) -> Result<()> {
+        todo!()
+    }
+    fn set_read_only(&mut self, read_only: bool) -> Result<()> {
+        self.conn.query_drop(if read_only {
+            "SET SESSION TRANSACTION READ ONLY"
+        } else {
+            "SET SESSION TRANSACTION READ WRITE"
+        })?;
+        Ok(())
+    }
+    fn set_autocommit(&mut self, autocommit: bool) -> Result<()> {
+        self.conn
+            .query_drop(format!("SET autocommit = {}", autocommit as u8))?;
+        Ok(())
+    }
+    fn set_time_zone(&mut self, time_zone: &str) -> Result<()> {
+        self.conn.exec_drop("SET time_zone = ?", (time_zone,))?;
+        Ok(())
+    }
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        // Only limits SELECT statements
+        let ms = timeout.map(|t| t.as_millis() as u64).unwrap_or(0);
+        self.conn
+            .exec_drop("SET SESSION max_execution_time = ?", (ms,))?;
+        Ok(())
+    }
+    fn set_server_output(&mut self, _enabled: bool) -> Result<()> {
+        Ok(())
+    }
+    fn take_messages(&mut self) -> Result<Vec<String>> {
+        Ok(mem::take(&mut self.messages))
+    }
+    fn session_info(&mut self) -> Result<SessionInfo> {
+        let (user, schema, host): (String, Option<String>, String) = self
+            .conn
+            .query_first("SELECT current_user(), database(), @@hostname")?
+            .ok_or_else(|| anyhow!("No session info"))?;
+        // The client library does not expose the transaction flag of the server status;
+        // without the PROCESS privilege the transaction is not visible
+        let transactions: Option<i64> = self
+            .conn
+            .query_first(
+                "SELECT count(*) FROM information_schema.innodb_trx \
+                 WHERE trx_mysql_thread_id = connection_id()",
+            )
+            .unwrap_or(None);
+        Ok(SessionInfo {
+            user,
+            schema: schema.unwrap_or_default(),
+            host,
+            transaction: match transactions {
+                Some(n) if n > 0 => TransactionState::Active,
+                _ => TransactionState::Idle,
+            },
+        })
+    }
+    fn cancel_handle(&self) -> CancelHandle {
+        let opts = self.opts.clone();
+        let id = self.conn.connection_id();
+        Box::new(move || {
+            let mut conn = mysql::Conn::new(opts.clone())?;
+            conn.query_drop(format!("KILL QUERY {}", id))?;
+            Ok(())
+        })
+    }
+}
+
+/// Maps errors reported by the server to `Error::Server`
+fn server_error(e: mysql::Error, statement: &str) -> anyhow::Error {
+    match e {
+        mysql::Error::MySqlError(e) => {
+            // Syntax errors quote the statement from the failing position on
+            let near = Regex::new(r"near '(?s)(.+)' at line \d+$").unwrap();
+            let position = near
+                .captures(&e.message)
+                .and_then(|c| statement.find(&c[1]))
+                .map(|i| statement[..i].chars().count());
+            anyhow!(Error::Server {
+                code: format!("{} ({})", e.code, e.state),
+                message: e.message,
+                detail: None,
+                hint: None,
+                constraint: None,
+                position,
+            })
+        }
+        e => e.into(),
+    }
+}
+
+/// Converts the nested objects of `EXPLAIN FORMAT=JSON` into plan nodes
+fn json_plan_node(operation: String, v: &serde_json::Value) -> PlanNode {
+    // Costs are given as strings
+    let number = |v: &serde_json::Value| {
+        v.as_f64()
+            .or_else(|| v.as_str().and_then(|s| s.parse().ok()))
+    };
+    let mut node = PlanNode {
+        operation,
+        ..Default::default()
+    };
+    let obj = match v.as_object() {
+        Some(o) => o,
+        None => return node,
+    };
+    for (k, c) in obj {
+        match k.as_str() {
+            "query_block" => node.children.push(json_plan_node(
+                format!("Query block #{}", c["select_id"]),
+                c,
+            )),
+            "table" => {
+                let mut operation = format!(
+                    "{} on {}",
+                    c["access_type"].as_str().unwrap_or("?"),
+                    c["table_name"].as_str().unwrap_or("?")
+                );
+                if let Some(key) = c["key"].as_str() {
+                    operation = format!("{} using {}", operation, key);
+                }
+                node.children.push(json_plan_node(operation, c));
+            }
+            "ordering_operation"
+            | "grouping_operation"
+            | "duplicates_removal"
+            | "windowing"
+            | "union_result"
+            | "materialized_from_subquery" => {
+                node.children.push(json_plan_node(k.replace('_', " "), c))
+            }
+            // Lists of objects, whose contents become children
+            "nested_loop"
+            | "query_specifications"
+            | "attached_subqueries"
+            | "optimized_away_subqueries" => {
+                for e in c.as_array().into_iter().flatten() {
+                    node.children
+                        .extend(json_plan_node(String::new(), e).children);
+                }
+            }
+            "cost_info" => {
+                node.cost = number(&c["query_cost"]).or_else(|| number(&c["prefix_cost"]))
+            }
+            "rows_examined_per_scan" => node.rows = number(c),
+            "attached_condition" => node
+                .details
+                .push(format!("Filter: {}", c.as_str().unwrap_or_default())),
+            "using_filesort" if c.as_bool() == Some(true) => {
+                node.details.push("Using filesort".into())
+            }
+            "using_temporary_table" if c.as_bool() == Some(true) => {
+                node.details.push("Using temporary table".into())
+            }
+            _ => {}
+        }
+    }
+    node
+}
+
+/// Parses the tree of `EXPLAIN ANALYZE`, whose lines look like
+/// `    -> Table scan on t  (cost=0.55 rows=3) (actual time=0.02..0.03 rows=3 loops=1)`
+fn tree_plan(tree: &str) -> PlanNode {
+    let estimate = Regex::new(r"\(cost=([\d.e+]+) rows=([\d.e+]+)\)").unwrap();
+    let actual =
+        Regex::new(r"\(actual time=[\d.e+]+\.\.([\d.e+]+) rows=([\d.e+]+) loops=(\d+)\)").unwrap();
+
+    let mut nodes = vec![];
+    // Ids of the last node on each indentation level
+    let mut levels: Vec<i64> = vec![];
+    for line in tree.lines() {
+        let text = line.trim_start();
+        let depth = (line.len() - text.len()) / 4;
+        let text = match text.strip_prefix("-> ") {
+            Some(t) => t,
+            None => continue,
+        };
+        let operation = text.split("  (").next().unwrap_or(text).to_string();
+        let mut node = PlanNode {
+            operation,
+            ..Default::default()
+        };
+        if let Some(c) = estimate.captures(text) {
+            node.cost = c[1].parse().ok();
+            node.rows = c[2].parse().ok();
+        }
+        if let Some(c) = actual.captures(text) {
+            let loops: f64 = c[3].parse().unwrap_or(1.0);
+            node.actual_time = c[1].parse::<f64>().ok().map(|t| t * loops);
+            node.actual_rows = c[2].parse::<f64>().ok().map(|r| r * loops);
+        }
+        let id = nodes.len() as i64;
+        levels.truncate(depth);
+        nodes.push((id, levels.last().cloned(), node));
+        levels.push(id);
+    }
+    PlanNode::from_flat(nodes)
+}
+
+fn conv(v: Option<mysql::Value>) -> Option<String> {
+    match v {
+        Some(mysql::Value::NULL) => None,
+        Some(mysql::Value::Bytes(x)) => Some(String::from_utf8_lossy(&x).to_string()),
+        Some(mysql::Value::Int(x)) => Some(format!("{}", x)),
+        Some(mysql::Value::Float(x)) => Some(format!("{}", x)),
+        Some(mysql::Value::Date(y, m, d, ho, mi, se, mic)) => {
+            let t = NaiveDate::from_ymd_opt(y.into(), m.into(), d.into())
+                .and_then(|d| d.and_hms_micro_opt(ho.into(), mi.into(), se.into(), mic))
+                .and_then(|t| Local.from_local_datetime(&t).single());
+            Some(t.map(|t| format!("{}", t)).unwrap_or_else(|| "x".into()))
+        }
+        _ => Some("x".into()),
+    }
+}
@@ -0,0 +1 @@
+SHOW ENGINE INNODB STATUS
\ No newline at end of file
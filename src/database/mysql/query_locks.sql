@@ -0,0 +1,9 @@
+select waiting_pid      as blocked_pid,
+       waiting_query    as blocked_statement,
+       blocking_pid,
+       blocking_query   as current_statement_in_blocking_process,
+       locked_table,
+       locked_index,
+       wait_age
+  from sys.innodb_lock_waits
+ order by wait_age_secs desc
\ No newline at end of file
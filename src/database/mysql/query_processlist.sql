@@ -0,0 +1 @@
+SHOW FULL PROCESSLIST
\ No newline at end of file
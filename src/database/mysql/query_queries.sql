@@ -0,0 +1,5 @@
+select id, time, user, db, info
+  from information_schema.processlist
+ where command = 'Query'
+   and id != connection_id()
+ order by time desc
\ No newline at end of file
@@ -0,0 +1,9 @@
+select id as process_id,
+       user as username,
+       db as database_name,
+       host as client_address,
+       command,
+       time,
+       state
+  from information_schema.processlist
+ order by id
\ No newline at end of file
@@ -0,0 +1,5 @@
+select table_schema,
+       round(sum(data_length + index_length) / 1024 / 1024, 1) as size_mb
+  from information_schema.tables
+ group by table_schema
+ order by sum(data_length + index_length) desc
\ No newline at end of file
@@ -1,17 +1,32 @@
+use super::CancelHandle;
 use super::Connection;
 use super::ConnectionParams;
-use super::{Column, QueryResult, Row};
+use super::Dialect;
+use super::Error;
+use super::{Column, Outcome, PlanNode, QueryResult, Row};
+use super::{SessionInfo, TransactionState};
+use crate::ui::theme::{print_table, Role};
+use anyhow::anyhow;
 use anyhow::Result;
 use chrono;
-use colored::Colorize;
 use oracle::sql_type::OracleType;
 use prettytable::format;
-use prettytable::{color, Attr, Cell, Row as OtherRow, Table};
+use prettytable::{Cell, Row as OtherRow, Table};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 pub struct OracleConnection {
     identifier: String,
-    conn: oracle::Connection,
+    /// Shared with the cancel handle during statements
+    conn: Arc<oracle::Connection>,
+    /// DBMS_OUTPUT is enabled and read after each statement
+    server_output: bool,
+    /// Statements run in read-only transactions
+    read_only: bool,
+    /// Commits after each statement; not left to the driver, so that `explain` can roll back
+    autocommit: bool,
     _params: ConnectionParams,
+    user_queries: Vec<super::StandardQuery>,
 }
 
 impl OracleConnection {
@@ -23,12 +38,16 @@ impl OracleConnection {
         } else {
             u
         };
-        let conn = oracle::Connection::connect(&p.username.unwrap(), &p.password.unwrap(), s)?;
+        let conn = oracle::Connection::connect(p.username.unwrap(), p.password.unwrap(), s)?;
 
         Ok(Self {
             identifier: identifier.to_string(),
-            conn: conn,
+            conn: Arc::new(conn),
+            server_output: false,
+            read_only: false,
+            autocommit: false,
             _params: params,
+            user_queries: vec![],
         })
     }
 
@@ -47,94 +66,160 @@ impl OracleConnection {
             let nl: Option<String> = row.get("NULLABLE").unwrap();
             let dd: Option<String> = row.get("DATA_DEFAULT").unwrap();
             table.add_row(OtherRow::new(vec![
-                Cell::new(&n)
-                    .with_style(Attr::Bold)
-                    .with_style(Attr::ForegroundColor(color::GREEN)),
+                Role::Title.cell(&n),
                 Cell::new(&d),
                 Cell::new(&format!("{}", dl)),
                 Cell::new(&dp.map(|v| format!("{}", v)).unwrap_or("".into())),
-                Cell::new(&nl.map(|v| format!("{}", v)).unwrap_or("".into())),
-                Cell::new(&dd.map(|v| format!("{}", v)).unwrap_or("".into())),
+                Cell::new(&nl.map(|v| v.to_string()).unwrap_or("".into())),
+                Cell::new(&dd.map(|v| v.to_string()).unwrap_or("".into())),
             ]));
         }
-        table.printstd();
+        print_table(&table);
 
         Ok(())
     }
+
+    /// Runs `f` in a read-only transaction of its own on read-only connections, as
+    /// Oracle has no read-only sessions. The transaction ends afterwards, so that the
+    /// next statement sees current data. Otherwise commits after `f` in autocommit mode.
+    fn in_transaction<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        if self.read_only {
+            self.conn.execute("SET TRANSACTION READ ONLY", &[])?;
+            let r = f();
+            self.conn.rollback()?;
+            return r;
+        }
+        let r = f()?;
+        if self.autocommit {
+            self.conn.commit()?;
+        }
+        Ok(r)
+    }
+
+    fn run_statement(&self, statement: &str) -> Result<Vec<Outcome>> {
+        let se = |e| server_error(e, statement);
+        let mut stmt = self.conn.statement(statement).build().map_err(se)?;
+        if stmt.is_query() {
+            let rows = stmt.query(&[]).map_err(se)?;
+            return Ok(vec![fetch(rows)]);
+        }
+        stmt.execute(&[]).map_err(se)?;
+        let mut outcomes = vec![];
+        if stmt.is_dml() {
+            outcomes.push(Outcome::Updated(stmt.row_count()?));
+        }
+        // Result sets returned with DBMS_SQL.RETURN_RESULT
+        while let Some(mut cursor) = stmt.implicit_result()? {
+            outcomes.push(fetch(cursor.query()?));
+        }
+        if outcomes.is_empty() {
+            outcomes.push(Outcome::Message(if stmt.is_plsql() {
+                "PL/SQL procedure successfully completed.".into()
+            } else {
+                "Statement executed.".into()
+            }));
+        }
+        Ok(outcomes)
+    }
+
+    /// The plan EXPLAIN PLAN writes into plan_table, as DBMS_XPLAN.DISPLAY shows it
+    fn planned(&self, statement: &str) -> Result<Vec<(i64, Option<i64>, PlanNode)>> {
+        self.conn
+            .execute(
+                &format!("EXPLAIN PLAN SET STATEMENT_ID = 'dbc' FOR {}", statement),
+                &[],
+            )
+            .map_err(|e| server_error(e, statement))?;
+        plan_rows(self.conn.query(include_str!("plan_table.sql"), &[])?)
+    }
+
+    /// Runs the statement with all statistics and returns the figures
+    /// DBMS_XPLAN.DISPLAY_CURSOR shows for it
+    fn analyzed(&self, statement: &str) -> Result<Vec<(i64, Option<i64>, PlanNode)>> {
+        // Without access to v$parameter the level is most likely the default
+        let level = self
+            .conn
+            .query_row_as::<String>(
+                "SELECT value FROM v$parameter WHERE name = 'statistics_level'",
+                &[],
+            )
+            .ok()
+            .filter(|l| l.chars().all(|c| c.is_ascii_alphabetic()))
+            .unwrap_or_else(|| "TYPICAL".into());
+        self.conn
+            .execute("ALTER SESSION SET STATISTICS_LEVEL = ALL", &[])?;
+        let rows = self
+            .run_statement(statement)
+            .and_then(|_| Ok(self.conn.query(include_str!("plan_statistics.sql"), &[])?))
+            .and_then(plan_rows);
+        let reset = self.conn.execute(
+            &format!("ALTER SESSION SET STATISTICS_LEVEL = {}", level),
+            &[],
+        );
+        let rows = rows?;
+        reset?;
+        Ok(rows)
+    }
 }
 
 impl Connection for OracleConnection {
+    fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    fn dialect(&self) -> Dialect {
+        Dialect::Oracle
+    }
+
     fn print_connection_info(&mut self) -> Result<()> {
         let client_ver = oracle::Version::client().unwrap();
 
         let (server_ver, banner) = self.conn.server_version().unwrap();
         println!(
             "Oracle: Client {} connected to database {}",
-            client_ver.to_string().yellow(),
-            server_ver.to_string().green()
+            Role::Value.paint(client_ver.to_string()),
+            Role::Value.paint(server_ver.to_string())
         );
-        println!("{}", banner.magenta());
+        println!("{}", Role::Heading.paint(banner));
 
         Ok(())
     }
 
     fn execute(&mut self, statement: &str) -> Result<u64> {
-        let r = self.conn.execute(statement, &[])?;
+        let r = self.in_transaction(|| Ok(self.conn.execute(statement, &[])?))?;
         Ok(r.row_count().unwrap())
     }
     fn query(&mut self, statement: &str) -> Result<QueryResult> {
         let rows = self.conn.query(statement, &[])?;
-        let ci = rows.column_info();
-        let columns: Vec<Column> = ci
-            .iter()
-            .map(|c| Column {
-                name: c.name().to_string(),
-            })
-            .collect();
-        Ok(QueryResult {
-            columns,
-            rows: rows
-                .map(|r| Row {
-                    data: r
-                        .unwrap()
-                        .sql_values()
-                        .iter()
-                        .map(|x| {
-                            if x.is_null().unwrap() {
-                                None
-                            } else {
-                                match x.oracle_type().unwrap() {
-                                    OracleType::Varchar2(_) => {
-                                        let s: String = x.get().unwrap();
-                                        Some(s)
-                                    }
-                                    OracleType::Int64 | OracleType::UInt64 => {
-                                        let y: i64 = x.get().unwrap();
-                                        Some(format!("{}", y))
-                                    }
-                                    OracleType::Number(_s, p) if *p == 0 => {
-                                        let y: i64 = x.get().unwrap();
-                                        Some(format!("{}", y))
-                                    }
-                                    OracleType::Number(_s, p) if *p > 0 => {
-                                        let y: f64 = x.get().unwrap();
-                                        Some(format!("{}", y))
-                                    }
-                                    OracleType::Timestamp(_) | OracleType::Date => {
-                                        let y: chrono::NaiveDateTime = x.get().unwrap();
-                                        Some(format!("{}", y))
-                                    }
-                                    _ => Some("???".into()),
-                                }
-                            }
-                        })
-                        .collect(),
-                })
-                .collect(),
-        })
+        Ok(result_set(rows))
+    }
+    fn run(&mut self, statement: &str) -> Result<Vec<Outcome>> {
+        self.in_transaction(|| self.run_statement(statement))
     }
-    fn prompt(&self) -> String {
-        format!("{} {}{} ", self.identifier.cyan(), "(ora)".magenta(), ">")
+    fn explain(&mut self, statement: &str, analyze: bool) -> Result<PlanNode> {
+        if self.read_only {
+            // A read-only transaction allows neither EXPLAIN PLAN nor savepoints. Analyzed
+            // statements are queries and run in one; plans are rolled back entirely.
+            let rows = if analyze {
+                self.in_transaction(|| self.analyzed(statement))
+            } else {
+                let rows = self.planned(statement);
+                self.conn.rollback()?;
+                rows
+            };
+            return Ok(PlanNode::from_flat(rows?));
+        }
+        // Drops the rows in plan_table and the changes of the analyzed statement
+        self.conn.execute("SAVEPOINT dbc_explain", &[])?;
+        let rows = if analyze {
+            self.analyzed(statement)
+        } else {
+            self.planned(statement)
+        };
+        let rolled_back = self.conn.execute("ROLLBACK TO SAVEPOINT dbc_explain", &[]);
+        let rows = rows?;
+        rolled_back?;
+        Ok(PlanNode::from_flat(rows))
     }
     fn list_tables(&mut self) -> std::result::Result<Vec<super::TableRef>, anyhow::Error> {
         let mut v: Vec<super::TableRef> = vec![];
@@ -150,10 +235,60 @@ impl Connection for OracleConnection {
             };
             v.push(tr);
         }
-        return Ok(v);
+        Ok(v)
+    }
+    fn list_columns(&mut self, table: &super::TableRef) -> Result<Vec<String>> {
+        // The empty schema is NULL in Oracle and means the own one
+        let rows = self.conn.query(
+            "select column_name from all_tab_columns
+              where table_name = upper(:1) and owner = nvl(upper(:2), user)
+              order by column_id",
+            &[&table.name, &table.schema],
+        )?;
+        let mut v = vec![];
+        for row in rows {
+            let c: String = row?.get(0)?;
+            v.push(c.to_lowercase());
+        }
+        Ok(v)
     }
     fn standard_queries(&self) -> Vec<super::StandardQuery> {
-        vec![]
+        let queries = vec![
+            super::StandardQuery::new(
+                "locks",
+                include_str!("query_locks.sql"),
+                "Blocked sessions and the sessions blocking them",
+            ),
+            super::StandardQuery::new(
+                "queries",
+                include_str!("query_queries.sql"),
+                "Statements running right now",
+            ),
+            super::StandardQuery::new(
+                "sizes",
+                include_str!("query_sizes.sql"),
+                "Size of the segments per owner, needs access to the DBA views",
+            ),
+            super::StandardQuery::new(
+                "sessions",
+                include_str!("query_sessions.sql"),
+                "Connected sessions",
+            ),
+            super::StandardQuery::new(
+                "tablespaces",
+                include_str!("query_tablespaces.sql"),
+                "Size and usage of the tablespaces, needs access to the DBA views",
+            ),
+            super::StandardQuery::new(
+                "invalid_objects",
+                include_str!("query_invalid_objects.sql"),
+                "Objects that need to be recompiled",
+            ),
+        ];
+        super::merge_queries(queries, &self.user_queries)
+    }
+    fn set_user_queries(&mut self, queries: Vec<super::StandardQuery>) {
+        self.user_queries = queries;
     }
     fn describe(&mut self, obj: &str) -> Result<()> {
         let obj = obj.to_uppercase();
@@ -177,12 +312,8 @@ impl Connection for OracleConnection {
         let mut table = Table::new();
         table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
         table.set_titles(OtherRow::new(vec![
-            Cell::new("name")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::GREEN)),
-            Cell::new("type")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::GREEN)),
+            Role::Title.cell("name"),
+            Role::Title.cell("type"),
         ]));
 
         let rows = self
@@ -195,7 +326,222 @@ impl Connection for OracleConnection {
             let t2: String = row.get(1).unwrap();
             table.add_row(OtherRow::new(vec![Cell::new(&t1), Cell::new(&t2)]));
         }
-        table.printstd();
+        print_table(&table);
+        Ok(())
+    }
+    fn set_read_only(&mut self, read_only: bool) -> Result<()> {
+        if read_only
+            && !self.read_only
+            && self.session_info()?.transaction != TransactionState::Idle
+        {
+            return Err(anyhow!(
+                "Commit or roll back the open transaction before switching to read-only"
+            ));
+        }
+        self.read_only = read_only;
+        Ok(())
+    }
+    fn autocommit(&self) -> bool {
+        self.autocommit
+    }
+    fn set_autocommit(&mut self, autocommit: bool) -> Result<()> {
+        self.autocommit = autocommit;
+        Ok(())
+    }
+    fn set_time_zone(&mut self, time_zone: &str) -> Result<()> {
+        self.conn.execute(
+            &format!(
+                "ALTER SESSION SET TIME_ZONE = '{}'",
+                time_zone.replace('\'', "''")
+            ),
+            &[],
+        )?;
+        Ok(())
+    }
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.conn.set_call_timeout(timeout)?;
         Ok(())
     }
+    fn set_server_output(&mut self, enabled: bool) -> Result<()> {
+        self.conn.execute(
+            if enabled {
+                "BEGIN DBMS_OUTPUT.ENABLE(NULL); END;"
+            } else {
+                "BEGIN DBMS_OUTPUT.DISABLE; END;"
+            },
+            &[],
+        )?;
+        self.server_output = enabled;
+        Ok(())
+    }
+    fn take_messages(&mut self) -> Result<Vec<String>> {
+        let mut lines = vec![];
+        if !self.server_output {
+            return Ok(lines);
+        }
+        let mut stmt = self
+            .conn
+            .statement("BEGIN DBMS_OUTPUT.GET_LINE(:line, :status); END;")
+            .build()?;
+        loop {
+            stmt.execute(&[&OracleType::Varchar2(32767), &OracleType::Int64])?;
+            let status: i64 = stmt.bind_value(2)?;
+            if status != 0 {
+                break;
+            }
+            let line: Option<String> = stmt.bind_value(1)?;
+            lines.push(line.unwrap_or_default());
+        }
+        Ok(lines)
+    }
+    fn session_info(&mut self) -> Result<SessionInfo> {
+        let (user, schema, host, transaction) =
+            self.conn
+                .query_row_as::<(String, String, Option<String>, Option<String>)>(
+                    "SELECT user, sys_context('USERENV', 'CURRENT_SCHEMA'), \
+                 sys_context('USERENV', 'SERVER_HOST'), dbms_transaction.local_transaction_id \
+                 FROM dual",
+                    &[],
+                )?;
+        Ok(SessionInfo {
+            user,
+            schema,
+            host: host.unwrap_or_default(),
+            transaction: match transaction {
+                Some(_) => TransactionState::Active,
+                None => TransactionState::Idle,
+            },
+        })
+    }
+    fn cancel_handle(&self) -> CancelHandle {
+        let conn = self.conn.clone();
+        Box::new(move || Ok(conn.break_execution()?))
+    }
+}
+
+/// Maps errors reported by the server to `Error::Server`
+fn server_error(e: oracle::Error, statement: &str) -> anyhow::Error {
+    match &e {
+        oracle::Error::OciError(db) | oracle::Error::DpiError(db) => {
+            let code = format!("ORA-{:05}", db.code());
+            let message = db.message();
+            // The offset is a byte offset, only set for parse errors
+            let offset = db.offset() as usize;
+            anyhow!(Error::Server {
+                message: message
+                    .strip_prefix(&format!("{}: ", code))
+                    .unwrap_or(message)
+                    .to_string(),
+                code,
+                detail: None,
+                hint: None,
+                constraint: None,
+                position: statement
+                    .get(..offset)
+                    .filter(|_| offset > 0)
+                    .map(|s| s.chars().count()),
+            })
+        }
+        _ => e.into(),
+    }
+}
+
+/// Reads the rows of plan_table.sql or plan_statistics.sql
+fn plan_rows(rows: oracle::ResultSet<oracle::Row>) -> Result<Vec<(i64, Option<i64>, PlanNode)>> {
+    let mut nodes = vec![];
+    for row in rows {
+        let row = row?;
+        let operation: String = row.get("OPERATION")?;
+        let options: Option<String> = row.get("OPTIONS")?;
+        let object: Option<String> = row.get("OBJECT_NAME")?;
+        let access: Option<String> = row.get("ACCESS_PREDICATES")?;
+        let filter: Option<String> = row.get("FILTER_PREDICATES")?;
+        let mut details = vec![];
+        if let Some(a) = access {
+            details.push(format!("Access: {}", a));
+        }
+        if let Some(f) = filter {
+            details.push(format!("Filter: {}", f));
+        }
+        let node = PlanNode {
+            operation: [
+                Some(operation),
+                options,
+                object.map(|o| format!("on {}", o)),
+            ]
+            .iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" "),
+            details,
+            cost: row.get("COST")?,
+            rows: row.get("CARDINALITY")?,
+            actual_rows: row.get("ACTUAL_ROWS")?,
+            actual_time: row.get("ACTUAL_TIME")?,
+            children: vec![],
+        };
+        nodes.push((row.get("ID")?, row.get("PARENT_ID")?, node));
+    }
+    Ok(nodes)
+}
+
+fn fetch(rows: oracle::ResultSet<oracle::Row>) -> Outcome {
+    let start = Instant::now();
+    let result = result_set(rows);
+    Outcome::Rows {
+        result,
+        fetch_time: start.elapsed(),
+    }
+}
+
+fn result_set(rows: oracle::ResultSet<oracle::Row>) -> QueryResult {
+    let ci = rows.column_info();
+    let columns: Vec<Column> = ci
+        .iter()
+        .map(|c| Column {
+            name: c.name().to_string(),
+        })
+        .collect();
+    QueryResult {
+        columns,
+        rows: rows
+            .map(|r| Row {
+                data: r
+                    .unwrap()
+                    .sql_values()
+                    .iter()
+                    .map(|x| {
+                        if x.is_null().unwrap() {
+                            None
+                        } else {
+                            match x.oracle_type().unwrap() {
+                                OracleType::Varchar2(_) => {
+                                    let s: String = x.get().unwrap();
+                                    Some(s)
+                                }
+                                OracleType::Int64 | OracleType::UInt64 => {
+                                    let y: i64 = x.get().unwrap();
+                                    Some(format!("{}", y))
+                                }
+                                OracleType::Number(_s, p) if *p == 0 => {
+                                    let y: i64 = x.get().unwrap();
+                                    Some(format!("{}", y))
+                                }
+                                OracleType::Number(_s, p) if *p > 0 => {
+                                    let y: f64 = x.get().unwrap();
+                                    Some(format!("{}", y))
+                                }
+                                OracleType::Timestamp(_) | OracleType::Date => {
+                                    let y: chrono::NaiveDateTime = x.get().unwrap();
+                                    Some(format!("{}", y))
+                                }
+                                _ => Some("???".into()),
+                            }
+                        }
+                    })
+                    .collect(),
+            })
+            .collect(),
+    }
 }
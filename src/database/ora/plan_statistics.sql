@@ -0,0 +1,8 @@
+select id, parent_id, operation, options, object_name, cost, cardinality,
+       last_output_rows actual_rows, last_elapsed_time / 1000 actual_time,
+       access_predicates, filter_predicates
+  from v$sql_plan_statistics_all
+ where (sql_id, child_number) = (select prev_sql_id, prev_child_number
+                                   from v$session
+                                  where sid = sys_context('userenv', 'sid'))
+ order by id
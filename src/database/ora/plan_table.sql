@@ -0,0 +1,5 @@
+select id, parent_id, operation, options, object_name, cost, cardinality,
+       null actual_rows, null actual_time, access_predicates, filter_predicates
+  from plan_table
+ where statement_id = 'dbc'
+ order by id
@@ -0,0 +1,7 @@
+select owner,
+       object_type,
+       object_name,
+       last_ddl_time
+  from all_objects
+ where status = 'INVALID'
+ order by owner, object_type, object_name
\ No newline at end of file
@@ -0,0 +1,20 @@
+select blocked.sid         as blocked_sid,
+       blocked.username    as blocked_user,
+       blocking.sid        as blocking_sid,
+       blocking.username   as blocking_user,
+       blocked.event       as wait_event,
+       blocked.seconds_in_wait,
+       o.owner || '.' || o.object_name as object,
+       blocked_sql.sql_text  as blocked_statement,
+       blocking_sql.sql_text as current_statement_in_blocking_session
+  from v$session blocked
+  join v$session blocking
+    on blocking.sid = blocked.blocking_session
+  left join all_objects o
+    on o.object_id = blocked.row_wait_obj#
+  left join v$sql blocked_sql
+    on blocked_sql.sql_id = blocked.sql_id and blocked_sql.child_number = blocked.sql_child_number
+  left join v$sql blocking_sql
+    on blocking_sql.sql_id = blocking.sql_id and blocking_sql.child_number = blocking.sql_child_number
+ where blocked.blocking_session is not null
+ order by blocked.seconds_in_wait desc
\ No newline at end of file
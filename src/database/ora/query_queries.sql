@@ -0,0 +1,12 @@
+select s.sid,
+       s.username,
+       numtodsinterval(s.last_call_et, 'SECOND') as age,
+       s.event,
+       q.sql_text
+  from v$session s
+  join v$sql q
+    on q.sql_id = s.sql_id and q.child_number = s.sql_child_number
+ where s.status = 'ACTIVE'
+   and s.type = 'USER'
+   and s.sid != sys_context('USERENV', 'SID')
+ order by s.last_call_et desc
\ No newline at end of file
@@ -0,0 +1,12 @@
+select sid,
+       serial# as serial,
+       username,
+       osuser,
+       machine,
+       program,
+       logon_time,
+       status,
+       event
+  from v$session
+ where type = 'USER'
+ order by logon_time
\ No newline at end of file
@@ -0,0 +1,5 @@
+select owner,
+       round(sum(bytes) / 1024 / 1024, 1) as size_mb
+  from dba_segments
+ group by owner
+ order by sum(bytes) desc
\ No newline at end of file
@@ -0,0 +1,15 @@
+select df.tablespace_name,
+       round(df.bytes / 1024 / 1024, 1) as size_mb,
+       round((df.bytes - nvl(fs.bytes, 0)) / 1024 / 1024, 1) as used_mb,
+       round(nvl(fs.bytes, 0) / 1024 / 1024, 1) as free_mb,
+       round((df.bytes - nvl(fs.bytes, 0)) * 100 / df.bytes, 1) as used_pct,
+       round(df.max_bytes / 1024 / 1024, 1) as max_mb
+  from (select tablespace_name, sum(bytes) as bytes,
+               sum(greatest(bytes, maxbytes)) as max_bytes
+          from dba_data_files
+         group by tablespace_name) df
+  left join (select tablespace_name, sum(bytes) as bytes
+               from dba_free_space
+              group by tablespace_name) fs
+    on fs.tablespace_name = df.tablespace_name
+ order by used_pct desc
\ No newline at end of file
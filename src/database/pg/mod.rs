@@ -1,25 +1,43 @@
+use super::tls::native_tls_connector;
+use super::CancelHandle;
 use super::Connection;
 use super::ConnectionParams;
+use super::Dialect;
 use super::Error;
-use super::{Column, QueryResult};
+use super::SslMode;
+use super::{Column, Outcome, PlanNode, QueryResult};
+use super::{SessionInfo, TransactionState};
+use crate::ui::theme::{print_table, Role};
 use anyhow::anyhow;
 use anyhow::Result;
 use byteorder::{BigEndian, ReadBytesExt};
 use chrono;
 use chrono::offset::FixedOffset;
-use colored::Colorize;
-use postgres::types::FromSql;
+use postgres::error::{DbError, ErrorPosition, SqlState};
+use postgres::fallible_iterator::FallibleIterator;
 use postgres::types::{accepts, Type};
-use postgres::{Client, NoTls, Row};
+use postgres::types::{FromSql, ToSql};
+use postgres::{Client, NoTls, Row, SimpleQueryMessage};
+use postgres_native_tls::MakeTlsConnector;
 use prettytable::format;
-use prettytable::{color, Attr, Cell, Row as OtherRow, Table};
+use prettytable::{Cell, Row as OtherRow, Table};
 use regex::Regex;
+use std::error::Error as _;
 use std::fmt::Display;
+use std::iter;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 pub struct PgConnection {
     identifier: String,
     client: Client,
-    _params: ConnectionParams,
+    /// Notices, filled by the notice callback of the client
+    messages: Arc<Mutex<Vec<String>>>,
+    params: ConnectionParams,
+    user_queries: Vec<super::StandardQuery>,
+    /// The last session info, still valid while a failed transaction rejects queries
+    session: SessionInfo,
 }
 
 impl PgConnection {
@@ -30,20 +48,40 @@ impl PgConnection {
         let c = re
             .captures(&u)
             .expect("Format of URL needs to be //host:port/db");
+        let sslmode = match params.ssl_mode() {
+            SslMode::Disable => "disable",
+            SslMode::Prefer => "prefer",
+            SslMode::Require | SslMode::VerifyCa | SslMode::VerifyFull => "require",
+        };
         let s = format!(
-            "host={} port={} user={} password={} dbname={}",
+            "host={} port={} user={} password={} dbname={} sslmode={}",
             &c[1],
             &c[2],
             p.username.unwrap(),
             p.password.unwrap(),
-            &c[3]
+            &c[3],
+            sslmode
         );
-        let client = Client::connect(&s, NoTls)?;
+        let messages = Arc::new(Mutex::new(vec![]));
+        let mut config: postgres::Config = s.parse()?;
+        let m = messages.clone();
+        config.notice_callback(move |n| {
+            m.lock()
+                .unwrap()
+                .push(format!("{}: {}", n.severity(), n.message()))
+        });
+        let client = match params.ssl_mode() {
+            SslMode::Disable => config.connect(NoTls)?,
+            _ => config.connect(MakeTlsConnector::new(native_tls_connector(&params)?))?,
+        };
 
         Ok(Self {
             identifier: identifier.to_string(),
             client,
-            _params: params,
+            messages,
+            params,
+            user_queries: vec![],
+            session: SessionInfo::default(),
         })
     }
 
@@ -52,8 +90,8 @@ impl PgConnection {
             .client
             .query(include_str!("view_definition.sql"), &[&obj])?;
 
-        println!("{}", "Definition:".magenta());
-        let row = rows.iter().nth(0).unwrap();
+        println!("{}", Role::Heading.paint("Definition:"));
+        let row = rows.first().unwrap();
         let d: String = row.get("definition");
         println!("{}", d);
         Ok(())
@@ -65,7 +103,7 @@ impl PgConnection {
                 .client
                 .query(include_str!("table_columns.sql"), &[&obj])?;
 
-            println!("{}", "Columns:".magenta());
+            println!("{}", Role::Heading.paint("Columns:"));
             let mut table = Table::new();
             table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
             for row in rows {
@@ -76,19 +114,17 @@ impl PgConnection {
                     None => "".into(),
                 };
                 table.add_row(OtherRow::new(vec![
-                    Cell::new(row.get("column_name"))
-                        .with_style(Attr::Bold)
-                        .with_style(Attr::ForegroundColor(color::GREEN)),
+                    Role::Title.cell(row.get("column_name")),
                     Cell::new(row.get("data_type")),
                     Cell::new(&max_length_str),
                     Cell::new(row.get("is_nullable")),
                     Cell::new(&default_value.unwrap_or("".into())),
                 ]));
             }
-            table.printstd();
+            print_table(&table);
         }
         {
-            println!("{}", "Foreign Keys:".magenta());
+            println!("{}", Role::Heading.paint("Foreign Keys:"));
             let rows = self
                 .client
                 .query(include_str!("foreign_keys.sql"), &[&obj])?;
@@ -99,9 +135,9 @@ impl PgConnection {
                 let my_column: String = row.get("column_name");
                 println!(
                     "  {} -> {} ({})",
-                    my_column.green(),
-                    format!("{}.{}", other_table_schema, other_table).blue(),
-                    columns.yellow()
+                    Role::Title.paint(my_column),
+                    Role::Value.paint(format!("{}.{}", other_table_schema, other_table)),
+                    Role::Value.paint(columns)
                 );
             }
         }
@@ -115,53 +151,69 @@ impl PgConnection {
         let rows = self
             .client
             .query(include_str!("sequence_data.sql"), &[&obj])?;
-        if let Some(row) = rows.iter().nth(0) {
+        if let Some(row) = rows.first() {
             let mut table = Table::new();
             table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
             table.add_row(OtherRow::new(vec![
-                Cell::new("name")
-                    .with_style(Attr::Bold)
-                    .with_style(Attr::ForegroundColor(color::GREEN)),
+                Role::Title.cell("name"),
                 Cell::new(row.get("sequencename")),
             ]));
             table.add_row(OtherRow::new(vec![
-                Cell::new("start_value")
-                    .with_style(Attr::Bold)
-                    .with_style(Attr::ForegroundColor(color::GREEN)),
+                Role::Title.cell("start_value"),
                 Cell::new(&row.get::<_, i64>("start_value").to_string()),
             ]));
             table.add_row(OtherRow::new(vec![
-                Cell::new("min_value")
-                    .with_style(Attr::Bold)
-                    .with_style(Attr::ForegroundColor(color::GREEN)),
+                Role::Title.cell("min_value"),
                 Cell::new(&row.get::<_, i64>("min_value").to_string()),
             ]));
             table.add_row(OtherRow::new(vec![
-                Cell::new("max_value")
-                    .with_style(Attr::Bold)
-                    .with_style(Attr::ForegroundColor(color::GREEN)),
+                Role::Title.cell("max_value"),
                 Cell::new(&row.get::<_, i64>("max_value").to_string()),
             ]));
             table.add_row(OtherRow::new(vec![
-                Cell::new("increment_by")
-                    .with_style(Attr::Bold)
-                    .with_style(Attr::ForegroundColor(color::GREEN)),
+                Role::Title.cell("increment_by"),
                 Cell::new(&row.get::<_, i64>("increment_by").to_string()),
             ]));
-            table.printstd();
+            print_table(&table);
         }
 
         Ok(())
     }
 }
 
+/// Maps errors reported by the server to `Error::Server`
+fn server_error(e: postgres::Error) -> anyhow::Error {
+    match e.source().and_then(|s| s.downcast_ref::<DbError>()) {
+        Some(db) => anyhow!(Error::Server {
+            code: db.code().code().to_string(),
+            message: db.message().to_string(),
+            detail: db.detail().map(String::from),
+            hint: db.hint().map(String::from),
+            constraint: db.constraint().map(String::from),
+            position: match db.position() {
+                Some(ErrorPosition::Original(p)) => Some(*p as usize - 1),
+                _ => None,
+            },
+        }),
+        None => e.into(),
+    }
+}
+
 impl Connection for PgConnection {
+    fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    fn dialect(&self) -> Dialect {
+        Dialect::Postgres
+    }
+
     fn print_connection_info(&mut self) -> Result<()> {
         let rows = self.client.query("show server_version;", &[])?;
 
-        if let Some(r) = rows.iter().nth(0) {
+        if let Some(r) = rows.first() {
             let s: String = r.get(0);
-            println!("Postgres: Connected to {}", s.green());
+            println!("Postgres: Connected to {}", Role::Value.paint(s));
         }
 
         Ok(())
@@ -173,12 +225,11 @@ impl Connection for PgConnection {
     }
     fn query(&mut self, statement: &str) -> Result<QueryResult> {
         let res = self.client.query(statement, &[])?;
-        if res.len() == 0 {
-            return Err(anyhow!(Error::NoResultError));
+        if res.is_empty() {
+            Err(anyhow!(Error::NoResultError))
         } else {
             let columns: Vec<Column> = res
-                .iter()
-                .nth(0)
+                .first()
                 .unwrap()
                 .columns()
                 .iter()
@@ -188,12 +239,59 @@ impl Connection for PgConnection {
                 .collect();
             Ok(QueryResult {
                 columns,
-                rows: res.iter().map(|r| row_values(r)).collect(),
+                rows: res.iter().map(row_values).collect(),
             })
         }
     }
-    fn prompt(&self) -> String {
-        format!("{} {}{} ", self.identifier.cyan(), "(pg)".magenta(), ">")
+    fn run(&mut self, statement: &str) -> Result<Vec<Outcome>> {
+        let stmt = self.client.prepare(statement).map_err(server_error)?;
+        if stmt.columns().is_empty() {
+            let rows_affected = self.client.execute(&stmt, &[]).map_err(server_error)?;
+            return Ok(vec![Outcome::Updated(rows_affected)]);
+        }
+        let rows = self
+            .client
+            .query_raw(&stmt, iter::empty::<&dyn ToSql>())
+            .map_err(server_error)?;
+        let start = Instant::now();
+        let rows = rows
+            .map(|r| Ok(row_values(&r)))
+            .collect()
+            .map_err(server_error)?;
+        Ok(vec![Outcome::Rows {
+            result: QueryResult {
+                columns: stmt
+                    .columns()
+                    .iter()
+                    .map(|c| Column {
+                        name: c.name().to_string(),
+                    })
+                    .collect(),
+                rows,
+            },
+            fetch_time: start.elapsed(),
+        }])
+    }
+    fn explain(&mut self, statement: &str, analyze: bool) -> Result<PlanNode> {
+        let options = if analyze {
+            "FORMAT JSON, ANALYZE"
+        } else {
+            "FORMAT JSON"
+        };
+        // The simple query protocol returns the json column as text
+        let messages = self
+            .client
+            .simple_query(&format!("EXPLAIN ({}) {}", options, statement))
+            .map_err(server_error)?;
+        let json = messages
+            .iter()
+            .find_map(|m| match m {
+                SimpleQueryMessage::Row(r) => r.get(0),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!(Error::NoResultError))?;
+        let plan: serde_json::Value = serde_json::from_str(json)?;
+        Ok(plan_node(&plan[0]["Plan"]))
     }
     fn list_tables(&mut self) -> Result<Vec<super::TableRef>> {
         let mut v: Vec<super::TableRef> = vec![];
@@ -211,26 +309,44 @@ impl Connection for PgConnection {
             v.push(tr);
         }
 
-        return Ok(v);
+        Ok(v)
+    }
+    fn list_columns(&mut self, table: &super::TableRef) -> Result<Vec<String>> {
+        let rows = self.client.query(
+            "select column_name from information_schema.columns
+              where lower(table_name) = lower($1) and ($2 = '' or lower(table_schema) = lower($2))
+              order by ordinal_position",
+            &[&table.name, &table.schema],
+        )?;
+        Ok(rows.iter().map(|r| r.get(0)).collect())
     }
     fn standard_queries(&self) -> Vec<super::StandardQuery> {
-        let s = super::StandardQuery {
-            name: "locks",
-            query: include_str!("query_locks.sql"),
-        };
-        let s2 = super::StandardQuery {
-            name: "queries",
-            query: include_str!("query_queries.sql"),
-        };
-        let s3 = super::StandardQuery {
-            name: "sizes",
-            query: include_str!("query_sizes.sql"),
-        };
-        let s4 = super::StandardQuery {
-            name: "sessions",
-            query: include_str!("query_sessions.sql"),
-        };
-        vec![s, s2, s3, s4]
+        let queries = vec![
+            super::StandardQuery::new(
+                "locks",
+                include_str!("query_locks.sql"),
+                "Blocked sessions and the sessions blocking them",
+            ),
+            super::StandardQuery::new(
+                "queries",
+                include_str!("query_queries.sql"),
+                "Statements running right now",
+            ),
+            super::StandardQuery::new(
+                "sizes",
+                include_str!("query_sizes.sql"),
+                "Size of the databases",
+            ),
+            super::StandardQuery::new(
+                "sessions",
+                include_str!("query_sessions.sql"),
+                "Connected sessions",
+            ),
+        ];
+        super::merge_queries(queries, &self.user_queries)
+    }
+    fn set_user_queries(&mut self, queries: Vec<super::StandardQuery>) {
+        self.user_queries = queries;
     }
     fn describe(&mut self, obj: &str) -> Result<()> {
         let obj = obj.to_ascii_lowercase();
@@ -244,8 +360,8 @@ impl Connection for PgConnection {
 
         println!(
             "{} is a {}",
-            obj.yellow(),
-            readable_type(&relkind).magenta()
+            Role::Value.paint(&obj),
+            Role::Detail.paint(readable_type(&relkind))
         );
 
         match relkind.as_ref() {
@@ -266,12 +382,8 @@ impl Connection for PgConnection {
         let mut table = Table::new();
         table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
         table.set_titles(OtherRow::new(vec![
-            Cell::new("name")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::GREEN)),
-            Cell::new("type")
-                .with_style(Attr::Bold)
-                .with_style(Attr::ForegroundColor(color::GREEN)),
+            Role::Title.cell("name"),
+            Role::Title.cell("type"),
         ]));
         for row in rows {
             table.add_row(OtherRow::new(vec![
@@ -279,14 +391,95 @@ impl Connection for PgConnection {
                 Cell::new(readable_type(row.get(1))),
             ]));
         }
-        table.printstd();
+        print_table(&table);
 
         Ok(())
     }
+    fn set_read_only(&mut self, read_only: bool) -> Result<()> {
+        self.client.batch_execute(if read_only {
+            "SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY"
+        } else {
+            "SET SESSION CHARACTERISTICS AS TRANSACTION READ WRITE"
+        })?;
+        Ok(())
+    }
+    fn set_autocommit(&mut self, autocommit: bool) -> Result<()> {
+        if autocommit {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Postgres always autocommits, use BEGIN to start a transaction"
+            ))
+        }
+    }
+    fn set_time_zone(&mut self, time_zone: &str) -> Result<()> {
+        self.client
+            .execute("SELECT set_config('TimeZone', $1, false)", &[&time_zone])?;
+        Ok(())
+    }
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        let ms = timeout.map(|t| t.as_millis()).unwrap_or(0).to_string();
+        self.client
+            .execute("SELECT set_config('statement_timeout', $1, false)", &[&ms])?;
+        Ok(())
+    }
+    fn set_server_output(&mut self, _enabled: bool) -> Result<()> {
+        Ok(())
+    }
+    fn take_messages(&mut self) -> Result<Vec<String>> {
+        Ok(mem::take(&mut *self.messages.lock().unwrap()))
+    }
+    fn session_info(&mut self) -> Result<SessionInfo> {
+        // now() is the start of the transaction, which is older than the statement inside
+        // one; with the extended protocol they differ outside of transactions as well
+        let res = self.client.simple_query(
+            "SELECT current_user, current_schema(), \
+             coalesce(host(inet_server_addr()), 'localhost'), now() <> statement_timestamp()",
+        );
+        match res {
+            Ok(messages) => {
+                let row = messages
+                    .iter()
+                    .find_map(|m| match m {
+                        SimpleQueryMessage::Row(r) => Some(r),
+                        _ => None,
+                    })
+                    .ok_or_else(|| anyhow!(Error::NoResultError))?;
+                let text = |i| row.get(i).unwrap_or_default().to_string();
+                self.session = SessionInfo {
+                    user: text(0),
+                    schema: text(1),
+                    host: text(2),
+                    transaction: if text(3) == "t" {
+                        TransactionState::Active
+                    } else {
+                        TransactionState::Idle
+                    },
+                };
+                Ok(self.session.clone())
+            }
+            Err(e) if e.code() == Some(&SqlState::IN_FAILED_SQL_TRANSACTION) => Ok(SessionInfo {
+                transaction: TransactionState::Failed,
+                ..self.session.clone()
+            }),
+            Err(e) => Err(e.into()),
+        }
+    }
+    fn cancel_handle(&self) -> CancelHandle {
+        let token = self.client.cancel_token();
+        let params = self.params.clone();
+        Box::new(move || {
+            match params.ssl_mode() {
+                SslMode::Disable => token.cancel_query(NoTls)?,
+                _ => token.cancel_query(MakeTlsConnector::new(native_tls_connector(&params)?))?,
+            }
+            Ok(())
+        })
+    }
 }
 
 fn readable_type(t: &str) -> &str {
-    match t.as_ref() {
+    match t {
         "v" => "view",
         "r" => "table",
         "i" => "index",
@@ -307,14 +500,14 @@ pub struct Interval {
 
 impl Display for Interval {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        write!(f, "{}", "P")?;
+        write!(f, "P")?;
         if self.months != 0 {
             write!(f, "{}M", self.months)?;
         }
         if self.days != 0 {
             write!(f, "{}D", self.days)?;
         }
-        write!(f, "{}", "T")?;
+        write!(f, "T")?;
         let hours = self.seconds / 3600;
         let minutes = (self.seconds % 3600) / 60;
         let seconds = self.seconds % 60;
@@ -357,11 +550,65 @@ fn interval_from_sql(mut buf: &[u8]) -> Result<Interval, Box<dyn std::error::Err
     })
 }
 
+fn plan_node(v: &serde_json::Value) -> PlanNode {
+    let s = |k: &str| v.get(k).and_then(|x| x.as_str());
+    let f = |k: &str| v.get(k).and_then(|x| x.as_f64());
+
+    let mut operation = s("Node Type").unwrap_or("?").to_string();
+    if let Some(j) = s("Join Type").filter(|j| *j != "Inner") {
+        operation = format!("{} ({})", operation, j);
+    }
+    if let Some(i) = s("Index Name") {
+        operation = format!("{} using {}", operation, i);
+    }
+    if let Some(r) = s("Relation Name") {
+        operation = format!("{} on {}", operation, r);
+        if let Some(a) = s("Alias").filter(|a| *a != r) {
+            operation = format!("{} {}", operation, a);
+        }
+    }
+
+    let mut details = vec![];
+    for k in &[
+        "Index Cond",
+        "Recheck Cond",
+        "Hash Cond",
+        "Merge Cond",
+        "Join Filter",
+        "Filter",
+    ] {
+        if let Some(c) = s(k) {
+            details.push(format!("{}: {}", k, c));
+        }
+    }
+    for k in &["Sort Key", "Group Key"] {
+        if let Some(keys) = v.get(*k).and_then(|x| x.as_array()) {
+            let keys: Vec<&str> = keys.iter().filter_map(|x| x.as_str()).collect();
+            details.push(format!("{}: {}", k, keys.join(", ")));
+        }
+    }
+
+    let loops = f("Actual Loops").unwrap_or(1.0);
+    PlanNode {
+        operation,
+        details,
+        cost: f("Total Cost"),
+        rows: f("Plan Rows"),
+        actual_rows: f("Actual Rows").map(|r| r * loops),
+        actual_time: f("Actual Total Time").map(|t| t * loops),
+        children: v
+            .get("Plans")
+            .and_then(|x| x.as_array())
+            .map(|a| a.iter().map(plan_node).collect())
+            .unwrap_or_default(),
+    }
+}
+
 fn row_values(row: &Row) -> super::Row {
     super::Row {
         data: (0..row.len())
             .map(|i| {
-                let c = row.columns().iter().nth(i);
+                let c = row.columns().get(i);
                 if let Some(c) = c {
                     match c.type_() {
                         &Type::TEXT | &Type::VARCHAR | &Type::NAME => {
@@ -390,7 +637,7 @@ fn row_values(row: &Row) -> super::Row {
                         }
                         &Type::BOOL => {
                             let x: Option<bool> = row.get(i);
-                            x.map(|y| format!("{}", if y { "true" } else { "false" }))
+                            x.map(|y| (if y { "true" } else { "false" }).to_string())
                         }
                         &Type::INTERVAL => {
                             let x: Option<Interval> = row.get(i);
@@ -0,0 +1,340 @@
+use super::CancelHandle;
+use super::Connection;
+use super::ConnectionParams;
+use super::Dialect;
+use super::Error;
+use super::{Column, Outcome, PlanNode, QueryResult};
+use super::{SessionInfo, TransactionState};
+use crate::ui::theme::Role;
+use anyhow::anyhow;
+use anyhow::Result;
+use postgres::fallible_iterator::FallibleIterator;
+use rusqlite::params;
+use rusqlite::types::ValueRef;
+use rusqlite::Row;
+use std::convert::TryInto;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub struct SqliteConnection {
+    identifier: String,
+    client: rusqlite::Connection,
+    timeout: Option<Duration>,
+    /// When the running statement exceeds the timeout, checked by the progress handler
+    deadline: Arc<Mutex<Option<Instant>>>,
+    params: ConnectionParams,
+    user_queries: Vec<super::StandardQuery>,
+}
+
+impl SqliteConnection {
+    pub fn create(identifier: &str, params: ConnectionParams) -> Result<Self> {
+        let u = params.clone().url.unwrap();
+        match u.as_ref() {
+            "memory" => {
+                let conn = rusqlite::Connection::open_in_memory()?;
+                eprintln!(
+                    "{}",
+                    Role::Warning
+                        .paint("Warning: This is an in-memory database. All changes will be lost.")
+                );
+                Ok(Self {
+                    identifier: identifier.to_string(),
+                    client: conn,
+                    timeout: None,
+                    deadline: Arc::new(Mutex::new(None)),
+                    params,
+                    user_queries: vec![],
+                })
+            }
+            x => {
+                let conn = rusqlite::Connection::open(Path::new(x))?;
+                Ok(Self {
+                    identifier: identifier.to_string(),
+                    client: conn,
+                    timeout: None,
+                    deadline: Arc::new(Mutex::new(None)),
+                    params,
+                    user_queries: vec![],
+                })
+            }
+        }
+    }
+}
+
+/// Limits the runtime of a statement until dropped, also when it fails
+struct Deadline(Arc<Mutex<Option<Instant>>>);
+
+impl Deadline {
+    fn start(deadline: &Arc<Mutex<Option<Instant>>>, timeout: Option<Duration>) -> Deadline {
+        *deadline.lock().unwrap() = timeout.map(|t| Instant::now() + t);
+        Deadline(deadline.clone())
+    }
+}
+
+impl Drop for Deadline {
+    fn drop(&mut self) {
+        *self.0.lock().unwrap() = None;
+    }
+}
+
+impl Connection for SqliteConnection {
+    fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    fn dialect(&self) -> Dialect {
+        Dialect::Sqlite
+    }
+
+    fn print_connection_info(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn execute(&mut self, statement: &str) -> Result<u64> {
+        let rows_affected = self.client.execute(statement, params![])?;
+        Ok(rows_affected.try_into().unwrap_or(0))
+    }
+    fn query(&mut self, statement: &str) -> Result<QueryResult> {
+        let mut stmt = self.client.prepare(statement)?;
+        let columns: Vec<Column> = stmt
+            .column_names()
+            .iter()
+            .map(|c| Column {
+                name: c.to_string(),
+            })
+            .collect();
+        let res = stmt.query(params![])?;
+        let column_count = columns.len();
+        Ok(QueryResult {
+            columns,
+            rows: res
+                .map(|r| Ok(row_values(r, column_count)))
+                .collect()
+                .unwrap(),
+        })
+    }
+    fn run(&mut self, statement: &str) -> Result<Vec<Outcome>> {
+        let _deadline = Deadline::start(&self.deadline, self.timeout);
+        let mut stmt = self.client.prepare(statement).map_err(server_error)?;
+        if stmt.column_count() == 0 {
+            let rows_affected = stmt.execute(params![]).map_err(server_error)?;
+            return Ok(vec![Outcome::Updated(
+                rows_affected.try_into().unwrap_or(0),
+            )]);
+        }
+        let columns: Vec<Column> = stmt
+            .column_names()
+            .iter()
+            .map(|c| Column {
+                name: c.to_string(),
+            })
+            .collect();
+        let column_count = columns.len();
+        let rows = stmt.query(params![]).map_err(server_error)?;
+        let start = Instant::now();
+        let rows = rows
+            .map(|r| Ok(row_values(r, column_count)))
+            .collect()
+            .map_err(server_error)?;
+        Ok(vec![Outcome::Rows {
+            result: QueryResult { columns, rows },
+            fetch_time: start.elapsed(),
+        }])
+    }
+    fn explain(&mut self, statement: &str, analyze: bool) -> Result<PlanNode> {
+        if analyze {
+            return Err(anyhow!("SQLite cannot analyze statements"));
+        }
+        let mut stmt = self
+            .client
+            .prepare(&format!("EXPLAIN QUERY PLAN {}", statement))
+            .map_err(server_error)?;
+        let nodes = stmt
+            .query(params![])
+            .map_err(server_error)?
+            .map(|r| {
+                let id: i64 = r.get(0)?;
+                let parent: i64 = r.get(1)?;
+                let detail: String = r.get(3)?;
+                Ok((
+                    id,
+                    Some(parent),
+                    PlanNode {
+                        operation: detail,
+                        ..Default::default()
+                    },
+                ))
+            })
+            .collect()
+            .map_err(server_error)?;
+        Ok(PlanNode::from_flat(nodes))
+    }
+    fn list_tables(&mut self) -> Result<Vec<super::TableRef>> {
+        let mut stmt = self
+            .client
+            .prepare("select null, name from sqlite_master where type = 'table'")?;
+        let res = stmt.query(params![])?;
+
+        let r = res
+            .map(|v| {
+                let schema: String = v.get(0).unwrap_or("".into());
+                let name: String = v.get(1).unwrap();
+                Ok(super::TableRef {
+                    schema: schema.to_lowercase(),
+                    name: name.to_lowercase(),
+                })
+            })
+            .collect()
+            .unwrap();
+        Ok(r)
+    }
+    fn list_columns(&mut self, table: &super::TableRef) -> Result<Vec<String>> {
+        let mut stmt = if table.schema.is_empty() {
+            self.client
+                .prepare("select name from pragma_table_info(?1)")?
+        } else {
+            self.client
+                .prepare("select name from pragma_table_info(?1, ?2)")?
+        };
+        let names = if table.schema.is_empty() {
+            stmt.query_map(params![table.name], |r| r.get(0))?
+                .collect::<rusqlite::Result<_>>()?
+        } else {
+            stmt.query_map(params![table.name, table.schema], |r| r.get(0))?
+                .collect::<rusqlite::Result<_>>()?
+        };
+        Ok(names)
+    }
+    fn standard_queries(&self) -> Vec<super::StandardQuery> {
+        let queries = vec![
+            super::StandardQuery::new(
+                "sizes",
+                include_str!("query_sizes.sql"),
+                "Size of the tables and indexes",
+            ),
+            super::StandardQuery::new(
+                "pragma",
+                include_str!("query_pragma.sql"),
+                "Settings of the database file",
+            ),
+        ];
+        super::merge_queries(queries, &self.user_queries)
+    }
+    fn set_user_queries(&mut self, queries: Vec<super::StandardQuery>) {
+        self.user_queries = queries;
+    }
+    fn describe(&mut self, _: &str) -> Result<()> {
+        todo!()
+    }
+    fn search(&mut self, _: &str) -> Result<()> {
+        todo!()
+    }
+    fn set_read_only(&mut self, read_only: bool) -> Result<()> {
+        self.client.pragma_update(None, "query_only", read_only)?;
+        Ok(())
+    }
+    fn set_autocommit(&mut self, autocommit: bool) -> Result<()> {
+        if autocommit {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "SQLite always autocommits, use BEGIN to start a transaction"
+            ))
+        }
+    }
+    fn set_time_zone(&mut self, _: &str) -> Result<()> {
+        Err(anyhow!("SQLite has no session time zone"))
+    }
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.timeout = timeout;
+        if timeout.is_some() {
+            let deadline = self.deadline.clone();
+            self.client.progress_handler(
+                1000,
+                Some(move || match *deadline.lock().unwrap() {
+                    Some(d) => Instant::now() > d,
+                    None => false,
+                }),
+            );
+        } else {
+            self.client.progress_handler(0, None::<fn() -> bool>);
+        }
+        Ok(())
+    }
+    fn set_server_output(&mut self, _enabled: bool) -> Result<()> {
+        Ok(())
+    }
+    fn take_messages(&mut self) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+    fn session_info(&mut self) -> Result<SessionInfo> {
+        Ok(SessionInfo {
+            user: String::new(),
+            schema: "main".into(),
+            host: self.params.url.clone().unwrap_or_default(),
+            transaction: if self.client.is_autocommit() {
+                TransactionState::Idle
+            } else {
+                TransactionState::Active
+            },
+        })
+    }
+    fn cancel_handle(&self) -> CancelHandle {
+        let handle = self.client.get_interrupt_handle();
+        Box::new(move || {
+            handle.interrupt();
+            Ok(())
+        })
+    }
+}
+
+/// Maps errors reported by SQLite to `Error::Server`
+fn server_error(e: rusqlite::Error) -> anyhow::Error {
+    match e {
+        rusqlite::Error::SqliteFailure(f, message) => anyhow!(Error::Server {
+            code: format!("SQLITE {}", f.extended_code),
+            message: message.unwrap_or_else(|| f.to_string()),
+            detail: None,
+            hint: None,
+            constraint: None,
+            position: None,
+        }),
+        // Syntax errors and unknown names, with the byte offset of the failing token
+        rusqlite::Error::SqlInputError {
+            error,
+            msg,
+            sql,
+            offset,
+        } => anyhow!(Error::Server {
+            code: format!("SQLITE {}", error.extended_code),
+            message: msg,
+            detail: None,
+            hint: None,
+            constraint: None,
+            position: offset
+                .try_into()
+                .ok()
+                .and_then(|o: usize| sql.get(..o))
+                .map(|s| s.chars().count()),
+        }),
+        e => e.into(),
+    }
+}
+
+fn row_values(row: &Row, column_count: usize) -> super::Row {
+    super::Row {
+        data: (0..column_count)
+            .map(|i| {
+                let v = row.get_ref_unwrap(i);
+                match v {
+                    ValueRef::Null => None,
+                    ValueRef::Integer(i) => Some(format!("{}", i)),
+                    ValueRef::Real(f) => Some(format!("{}", f)),
+                    ValueRef::Text(t) => Some(format!("{}", String::from_utf8_lossy(t))),
+                    ValueRef::Blob(_t) => Some("???".into()),
+                }
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,11 @@
+select 'journal_mode' as pragma, journal_mode as value from pragma_journal_mode
+union all select 'synchronous', synchronous from pragma_synchronous
+union all select 'foreign_keys', foreign_keys from pragma_foreign_keys
+union all select 'page_size', page_size from pragma_page_size
+union all select 'page_count', page_count from pragma_page_count
+union all select 'freelist_count', freelist_count from pragma_freelist_count
+union all select 'cache_size', cache_size from pragma_cache_size
+union all select 'auto_vacuum', auto_vacuum from pragma_auto_vacuum
+union all select 'encoding', encoding from pragma_encoding
+union all select 'user_version', user_version from pragma_user_version
+union all select 'busy_timeout', timeout from pragma_busy_timeout
\ No newline at end of file
@@ -0,0 +1,4 @@
+select name, sum(pgsize) as bytes, sum(ncell) as cells
+  from dbstat
+ group by name
+ order by sum(pgsize) desc
\ No newline at end of file
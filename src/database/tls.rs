@@ -0,0 +1,57 @@
+use super::ConnectionParams;
+use anyhow::Context;
+use anyhow::Result;
+use native_tls::{Certificate, Identity, TlsConnector};
+use std::fs;
+
+/// Builds a TLS connector honoring the `ssl*` settings of the connection
+pub fn native_tls_connector(params: &ConnectionParams) -> Result<TlsConnector> {
+    let mut builder = TlsConnector::builder();
+
+    if let Some(f) = &params.sslrootcert {
+        let pem = fs::read(f).with_context(|| format!("Cannot read CA bundle {}", f))?;
+        for cert in split_pem_certificates(&pem) {
+            builder.add_root_certificate(Certificate::from_pem(&cert)?);
+        }
+    }
+
+    match (&params.sslcert, &params.sslkey) {
+        (Some(cert), Some(key)) => {
+            let cert_pem = fs::read(cert)
+                .with_context(|| format!("Cannot read client certificate {}", cert))?;
+            let key_pem =
+                fs::read(key).with_context(|| format!("Cannot read client key {}", key))?;
+            // native-tls only reads PKCS#8 keys, not `BEGIN RSA PRIVATE KEY` ones
+            let identity = Identity::from_pkcs8(&cert_pem, &key_pem).with_context(|| {
+                format!(
+                    "Cannot load client key {}, it has to be in PKCS#8 format \
+                     (convert it with `openssl pkcs8 -topk8 -nocrypt -in {} -out <file>`)",
+                    key, key
+                )
+            })?;
+            builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => anyhow::bail!("sslcert and sslkey have to be given together"),
+    }
+
+    builder.danger_accept_invalid_certs(!params.ssl_verify_certificate());
+    builder.danger_accept_invalid_hostnames(!params.ssl_verify_hostname());
+
+    Ok(builder.build()?)
+}
+
+/// Splits a PEM bundle into its single certificates, as native-tls only
+/// reads the first one of a file
+fn split_pem_certificates(pem: &[u8]) -> Vec<Vec<u8>> {
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let text = String::from_utf8_lossy(pem);
+    let mut certs = vec![];
+    let mut rest: &str = &text;
+    while let Some(end) = rest.find(END) {
+        certs.push(rest[..end + END.len()].trim().as_bytes().to_vec());
+        rest = &rest[end + END.len()..];
+    }
+    certs
+}
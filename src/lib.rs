@@ -2,4 +2,5 @@ pub mod commands;
 pub mod config;
 pub mod database;
 pub mod nu_plugin_sql;
+pub mod sql;
 pub mod ui;
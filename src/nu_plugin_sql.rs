@@ -1,7 +1,7 @@
 use nu_plugin::{EvaluatedCall, LabeledError, Plugin};
 use nu_protocol::{Category, PluginExample, PluginSignature, SyntaxShape, Value};
 
-use crate::{database::create_connection, ui::DbcClient};
+use crate::database::create_connection;
 pub struct Sql;
 
 impl Plugin for Sql {
@@ -55,18 +55,17 @@ impl Sql {
             span: Some(call.head),
         })?;
 
-        let params = config.get(&identifier).ok_or(LabeledError {
+        let params = config.connection(&identifier).map_err(|e| LabeledError {
             label: "Database identifier not found".to_string(),
-            msg: "No such identifier".to_string(),
+            msg: format!("{}", e),
             span: call.nth(0).map(|x| x.span().unwrap()),
         })?;
 
-        let mut conn =
-            create_connection(&identifier, params.clone()).map_err(|e| LabeledError {
-                label: "Create connection error".to_string(),
-                msg: format!("{:?}", e),
-                span: Some(call.head),
-            })?;
+        let mut conn = create_connection(&identifier, params).map_err(|e| LabeledError {
+            label: "Create connection error".to_string(),
+            msg: format!("{:?}", e),
+            span: Some(call.head),
+        })?;
 
         let res = conn.query(&query).map_err(|e| LabeledError {
             label: "Query error".to_string(),
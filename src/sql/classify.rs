@@ -0,0 +1,545 @@
+use super::lexer::{significant_tokens, tokenize, Token, TokenKind};
+use crate::database::Dialect;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatementKind {
+    /// Reads data: SELECT, VALUES, SHOW, EXPLAIN, ...
+    Query,
+    /// Modifies data: INSERT, UPDATE, DELETE, MERGE, ...
+    Dml,
+    /// Modifies the schema: CREATE, ALTER, DROP, TRUNCATE, GRANT, ...
+    Ddl,
+    /// BEGIN, COMMIT, ROLLBACK, SAVEPOINT, ...
+    Transaction,
+    /// PL/SQL blocks, stored program units and Postgres `DO` blocks
+    Block,
+    /// Changes session state: SET, USE, ALTER SESSION
+    Session,
+    /// Procedure calls and everything not recognized
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classification {
+    pub kind: StatementKind,
+    /// Whether executing the statement yields a result set
+    pub returns_rows: bool,
+    /// The keyword determining the kind, upper case
+    pub keyword: String,
+    /// Whether a WHERE clause exists outside of subqueries
+    pub has_where: bool,
+    /// Whether the statement may switch off the read-only mode of the session
+    pub lifts_read_only: bool,
+}
+
+impl Classification {
+    /// Whether the statement may change data or schema
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self.kind,
+            StatementKind::Dml | StatementKind::Ddl | StatementKind::Block | StatementKind::Other
+        )
+    }
+
+    /// Whether a read-only connection refuses the statement
+    pub fn violates_read_only(&self) -> bool {
+        self.is_write() || self.lifts_read_only
+    }
+}
+
+const DDL_KEYWORDS: &[&str] = &[
+    "CREATE",
+    "ALTER",
+    "DROP",
+    "TRUNCATE",
+    "RENAME",
+    "COMMENT",
+    "GRANT",
+    "REVOKE",
+    "ANALYZE",
+    "VACUUM",
+    "REINDEX",
+    "CLUSTER",
+    "ATTACH",
+    "DETACH",
+    "PURGE",
+    "FLASHBACK",
+];
+
+const TRANSACTION_KEYWORDS: &[&str] = &[
+    "COMMIT",
+    "ROLLBACK",
+    "SAVEPOINT",
+    "RELEASE",
+    "START",
+    "END",
+    "ABORT",
+];
+
+/// Keywords starting the statement an `EXPLAIN` is given
+const EXPLAINABLE_KEYWORDS: &[&str] = &[
+    "SELECT", "INSERT", "UPDATE", "DELETE", "MERGE", "REPLACE", "WITH", "VALUES", "TABLE",
+    "CREATE", "EXECUTE", "DECLARE",
+];
+
+/// Object types whose CREATE statement contains PL/SQL
+const PLSQL_OBJECTS: &[&str] = &["PROCEDURE", "FUNCTION", "PACKAGE", "TRIGGER", "TYPE"];
+
+/// The object type of a CREATE statement, after `OR REPLACE` and `[NON]EDITIONABLE`
+fn created_object<'a>(top: &[Token<'a>]) -> Option<Token<'a>> {
+    let mut words = top.iter().skip(1);
+    let mut t = words.next()?;
+    if t.is_keyword("OR") {
+        t = words.nth(1)?;
+    }
+    if t.is_keyword("EDITIONABLE") || t.is_keyword("NONEDITIONABLE") {
+        t = words.next()?;
+    }
+    Some(*t)
+}
+
+/// Tokens at parenthesis depth zero
+fn top_level<'a>(tokens: &[Token<'a>]) -> Vec<Token<'a>> {
+    let mut depth = 0;
+    let mut res = vec![];
+    for t in tokens {
+        match t.kind {
+            TokenKind::OpenParen => depth += 1,
+            TokenKind::CloseParen => depth -= 1,
+            _ if depth == 0 => res.push(*t),
+            _ => {}
+        }
+    }
+    res
+}
+
+/// Skips the common table expressions of a WITH clause, returns the main statement keyword
+fn main_keyword_after_with<'a>(top: &[Token<'a>]) -> Option<Token<'a>> {
+    top.iter()
+        .skip(1)
+        .find(|t| {
+            [
+                "SELECT", "INSERT", "UPDATE", "DELETE", "MERGE", "VALUES", "TABLE",
+            ]
+            .iter()
+            .any(|k| t.is_keyword(k))
+        })
+        .copied()
+}
+
+/// The statement `EXPLAIN ANALYZE` executes; None for plain EXPLAIN
+fn analyzed_statement<'s>(sql: &'s str, tokens: &[Token<'s>]) -> Option<&'s str> {
+    let start = tokens
+        .iter()
+        .position(|t| EXPLAINABLE_KEYWORDS.iter().any(|k| t.is_keyword(k)))?;
+    // The options are given as words or in parentheses
+    tokens[..start]
+        .iter()
+        .any(|t| t.is_keyword("ANALYZE") || t.is_keyword("ANALYSE"))
+        .then(|| &sql[tokens[start].start..])
+}
+
+/// Names of the settings behind read-only sessions, like Postgres'
+/// `default_transaction_read_only` or SQLite's `query_only`
+fn names_read_only(t: &Token) -> bool {
+    let s = t.text.to_ascii_lowercase();
+    s.contains("read_only") || s.contains("query_only")
+}
+
+pub fn classify(sql: &str, dialect: Dialect) -> Classification {
+    let tokens = significant_tokens(sql, dialect);
+    if tokens
+        .first()
+        .map(|t| t.is_keyword("EXPLAIN"))
+        .unwrap_or(false)
+    {
+        if let Some(statement) = analyzed_statement(sql, &tokens) {
+            // Executes the statement, but returns the plan instead of what it returns
+            return Classification {
+                returns_rows: true,
+                ..classify(statement, dialect)
+            };
+        }
+    }
+    // Leading parentheses, as in `(select ...) union (select ...)`
+    let first = tokens
+        .iter()
+        .position(|t| t.kind != TokenKind::OpenParen)
+        .unwrap_or(tokens.len());
+    let top = top_level(&tokens[first..]);
+    let top = if first > 0 {
+        // The statement itself sits in parentheses, classify by its first keyword only
+        tokens[first..].to_vec()
+    } else {
+        top
+    };
+
+    let mut keyword_token = top.first().copied();
+    let mut modifying_cte = false;
+    if keyword_token.map(|t| t.is_keyword("WITH")).unwrap_or(false) {
+        keyword_token = main_keyword_after_with(&top).or(keyword_token);
+        // Postgres allows data-modifying statements inside the CTEs
+        modifying_cte = tokens.iter().any(|t| {
+            ["INSERT", "UPDATE", "DELETE", "MERGE"]
+                .iter()
+                .any(|k| t.is_keyword(k))
+        });
+    }
+    let keyword = keyword_token
+        .map(|t| t.text.to_ascii_uppercase())
+        .unwrap_or_default();
+    let second = top
+        .get(1)
+        .map(|t| t.text.to_ascii_uppercase())
+        .unwrap_or_default();
+    let has_where = top.iter().any(|t| t.is_keyword("WHERE"));
+    let has_returning = top.iter().any(|t| t.is_keyword("RETURNING"));
+
+    let kind = match keyword.as_str() {
+        _ if modifying_cte => StatementKind::Dml,
+        "SELECT" | "VALUES" | "TABLE" | "SHOW" | "EXPLAIN" | "DESCRIBE" | "DESC" => {
+            StatementKind::Query
+        }
+        "PRAGMA" if dialect == Dialect::Sqlite => {
+            if top
+                .iter()
+                .any(|t| t.kind == TokenKind::Operator && t.text == "=")
+            {
+                StatementKind::Session
+            } else {
+                StatementKind::Query
+            }
+        }
+        "INSERT" | "UPDATE" | "DELETE" | "MERGE" | "REPLACE" | "UPSERT" | "COPY" | "LOAD" => {
+            StatementKind::Dml
+        }
+        "BEGIN" | "DECLARE" if dialect == Dialect::Oracle => StatementKind::Block,
+        "BEGIN" => StatementKind::Transaction,
+        "DO" if dialect == Dialect::Postgres => StatementKind::Block,
+        "SET" if second == "TRANSACTION" => StatementKind::Transaction,
+        "SET" | "USE" | "RESET" => StatementKind::Session,
+        "ALTER" if second == "SESSION" => StatementKind::Session,
+        "CREATE"
+            if dialect == Dialect::Oracle
+                && created_object(&top)
+                    .map(|t| PLSQL_OBJECTS.iter().any(|k| t.is_keyword(k)))
+                    .unwrap_or(false) =>
+        {
+            StatementKind::Block
+        }
+        k if DDL_KEYWORDS.contains(&k) => StatementKind::Ddl,
+        k if TRANSACTION_KEYWORDS.contains(&k) => StatementKind::Transaction,
+        _ => StatementKind::Other,
+    };
+
+    let returns_rows = match kind {
+        StatementKind::Query => true,
+        // Oracle's RETURNING ... INTO binds variables instead of returning rows
+        StatementKind::Dml => {
+            (has_returning && dialect != Dialect::Oracle) || (modifying_cte && keyword == "SELECT")
+        }
+        _ => false,
+    };
+
+    let lifts_read_only = match kind {
+        StatementKind::Session | StatementKind::Transaction => {
+            tokens
+                .windows(2)
+                .any(|w| w[0].is_keyword("READ") && w[1].is_keyword("WRITE"))
+                || (keyword == "RESET" && second == "ALL")
+                || tokens.iter().any(names_read_only)
+        }
+        // SQLite's `PRAGMA query_only(0)`
+        StatementKind::Query if keyword == "PRAGMA" => {
+            tokens.iter().any(names_read_only)
+                && tokens.iter().any(|t| t.kind == TokenKind::OpenParen)
+        }
+        // Postgres' `SELECT set_config('default_transaction_read_only', ...)`
+        StatementKind::Query => tokens
+            .iter()
+            .any(|t| t.kind == TokenKind::String && names_read_only(t)),
+        _ => false,
+    };
+
+    Classification {
+        kind,
+        returns_rows,
+        keyword,
+        has_where,
+        lifts_read_only,
+    }
+}
+
+/// The table name following the first top-level FROM (or INTO/UPDATE), schema-qualified if given
+pub fn main_table(sql: &str, dialect: Dialect) -> Option<String> {
+    let tokens = significant_tokens(sql, dialect);
+    let top = top_level(&tokens);
+    let i = top
+        .iter()
+        .position(|t| t.is_keyword("FROM") || t.is_keyword("INTO") || t.is_keyword("UPDATE"))?;
+    let mut name = top.get(i + 1)?.identifier()?;
+    let mut j = i + 2;
+    while top
+        .get(j)
+        .map(|t| t.kind == TokenKind::Dot)
+        .unwrap_or(false)
+    {
+        name = format!("{}.{}", name, top.get(j + 1)?.identifier()?);
+        j += 2;
+    }
+    Some(name)
+}
+
+/// Removes trailing semicolons, except the one ending a PL/SQL block
+pub fn strip_terminator(sql: &str, dialect: Dialect) -> &str {
+    let sql = sql.trim();
+    if classify(sql, dialect).kind == StatementKind::Block && dialect == Dialect::Oracle {
+        sql.trim_end_matches('/').trim_end()
+    } else {
+        sql.trim_end_matches(';').trim_end()
+    }
+}
+
+/// Splits input into its statements at top-level semicolons; a PL/SQL block
+/// takes the rest of the input, as its body contains semicolons itself
+pub fn split_statements(sql: &str, dialect: Dialect) -> Vec<&str> {
+    let mut statements = vec![];
+    let mut start = 0;
+    for t in tokenize(sql, dialect) {
+        if t.kind != TokenKind::Semicolon {
+            continue;
+        }
+        let statement = &sql[start..t.end()];
+        if dialect == Dialect::Oracle && classify(statement, dialect).kind == StatementKind::Block {
+            break;
+        }
+        // Empty statements are left out
+        if significant_tokens(statement, dialect)
+            .iter()
+            .any(|t| t.kind != TokenKind::Semicolon)
+        {
+            statements.push(statement.trim());
+        }
+        start = t.end();
+    }
+    let rest = &sql[start..];
+    if !significant_tokens(rest, dialect).is_empty() {
+        statements.push(rest.trim());
+    }
+    statements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(sql: &str, dialect: Dialect) -> StatementKind {
+        classify(sql, dialect).kind
+    }
+
+    #[test]
+    fn classifies_statements() {
+        let pg = Dialect::Postgres;
+        assert_eq!(kind("select 1", pg), StatementKind::Query);
+        assert_eq!(
+            kind("(select 1) union (select 2)", pg),
+            StatementKind::Query
+        );
+        assert_eq!(
+            kind("with t as (select 1) select * from t", pg),
+            StatementKind::Query
+        );
+        assert_eq!(
+            kind("with d as (delete from t returning *) select * from d", pg),
+            StatementKind::Dml
+        );
+        assert_eq!(kind("insert into t values (1)", pg), StatementKind::Dml);
+        assert_eq!(kind("drop table t", pg), StatementKind::Ddl);
+        assert_eq!(kind("begin", pg), StatementKind::Transaction);
+        assert_eq!(kind("do $$ begin end $$", pg), StatementKind::Block);
+        assert_eq!(kind("set search_path = app", pg), StatementKind::Session);
+        assert_eq!(kind("call p()", pg), StatementKind::Other);
+        assert_eq!(
+            kind("begin null; end;", Dialect::Oracle),
+            StatementKind::Block
+        );
+        assert_eq!(
+            kind(
+                "create or replace procedure p as begin null; end;",
+                Dialect::Oracle
+            ),
+            StatementKind::Block
+        );
+        assert_eq!(
+            kind(
+                "alter session set nls_date_format = 'YYYY'",
+                Dialect::Oracle
+            ),
+            StatementKind::Session
+        );
+        assert_eq!(
+            kind("pragma table_info(t)", Dialect::Sqlite),
+            StatementKind::Query
+        );
+        assert_eq!(
+            kind("pragma foreign_keys = on", Dialect::Sqlite),
+            StatementKind::Session
+        );
+    }
+
+    #[test]
+    fn ignores_keywords_in_comments_and_strings() {
+        let pg = Dialect::Postgres;
+        assert_eq!(
+            kind("-- delete\nselect 'drop table t'", pg),
+            StatementKind::Query
+        );
+        assert_eq!(
+            kind("/* update /* nested */ t */ select 1", pg),
+            StatementKind::Query
+        );
+        assert_eq!(
+            kind("# delete\nselect 1", Dialect::Mysql),
+            StatementKind::Query
+        );
+        let c = classify("delete from t -- where a = 1", pg);
+        assert!(!c.has_where);
+        let c = classify("delete from t where a in (select b from u where c)", pg);
+        assert!(c.has_where);
+        let c = classify("update t set a = (select b from u where c)", pg);
+        assert!(!c.has_where);
+    }
+
+    #[test]
+    fn classifies_explain_analyze_by_its_statement() {
+        let pg = Dialect::Postgres;
+        let c = classify("explain delete from t", pg);
+        assert_eq!(c.kind, StatementKind::Query);
+        for sql in [
+            "explain analyze delete from t",
+            "EXPLAIN (ANALYZE, BUFFERS) delete from t",
+            "explain analyse verbose delete from t",
+        ] {
+            let c = classify(sql, pg);
+            assert_eq!(c.kind, StatementKind::Dml, "{}", sql);
+            assert_eq!(c.keyword, "DELETE");
+            assert!(c.returns_rows);
+            assert!(!c.has_where);
+        }
+        let c = classify("explain analyze select * from t", Dialect::Mysql);
+        assert_eq!(c.kind, StatementKind::Query);
+    }
+
+    #[test]
+    fn detects_statements_lifting_read_only() {
+        let lifts = |sql: &str, dialect| classify(sql, dialect).violates_read_only();
+        let pg = Dialect::Postgres;
+        assert!(lifts("set default_transaction_read_only = off", pg));
+        assert!(lifts(
+            "SET SESSION CHARACTERISTICS AS TRANSACTION READ WRITE",
+            pg
+        ));
+        assert!(lifts("begin read write", pg));
+        assert!(lifts("reset all", pg));
+        assert!(lifts(
+            "select set_config('default_transaction_read_only', 'off', false)",
+            pg
+        ));
+        assert!(lifts("set session transaction read write", Dialect::Mysql));
+        assert!(lifts(
+            "set @@session.transaction_read_only = 0",
+            Dialect::Mysql
+        ));
+        assert!(lifts("pragma query_only = 0", Dialect::Sqlite));
+        assert!(lifts("pragma query_only(false)", Dialect::Sqlite));
+        assert!(!lifts("set search_path = app", pg));
+        assert!(!lifts("set transaction read only", pg));
+        assert!(!lifts("begin", pg));
+        assert!(!lifts("select * from read_only_users", pg));
+        assert!(!lifts("pragma query_only", Dialect::Sqlite));
+    }
+
+    #[test]
+    fn splits_statements() {
+        let pg = Dialect::Postgres;
+        assert_eq!(
+            split_statements("select 1; select ';'; -- ;\n select 2", pg),
+            vec!["select 1;", "select ';';", "-- ;\n select 2"]
+        );
+        assert_eq!(
+            split_statements("select 1;; ;select \"a;b\" from t;", pg),
+            vec!["select 1;", "select \"a;b\" from t;"]
+        );
+        assert_eq!(
+            split_statements(
+                "create function f() returns int as $body$ select 1; $body$ language sql; select 2",
+                pg
+            ),
+            vec![
+                "create function f() returns int as $body$ select 1; $body$ language sql;",
+                "select 2"
+            ]
+        );
+        assert_eq!(
+            split_statements("select 'it''s; here' /* ; */ from t", pg),
+            vec!["select 'it''s; here' /* ; */ from t"]
+        );
+        assert_eq!(
+            split_statements("select 'a\\';' from t; select 2", Dialect::Mysql),
+            vec!["select 'a\\';' from t;", "select 2"]
+        );
+    }
+
+    #[test]
+    fn keeps_plsql_blocks_together() {
+        let ora = Dialect::Oracle;
+        assert_eq!(
+            split_statements("select 1 from dual; begin x := 1; y := q'[;]'; end;", ora),
+            vec!["select 1 from dual;", "begin x := 1; y := q'[;]'; end;"]
+        );
+        assert_eq!(
+            split_statements(
+                "create or replace editionable type t as object (x int); select 1 from dual;",
+                ora
+            ),
+            vec!["create or replace editionable type t as object (x int); select 1 from dual;"]
+        );
+        // Columns named like PL/SQL objects do not make a view a block
+        assert_eq!(
+            split_statements(
+                "create view v as select type from t; select 1 from dual;",
+                ora
+            ),
+            vec![
+                "create view v as select type from t;",
+                "select 1 from dual;"
+            ]
+        );
+        assert_eq!(
+            strip_terminator("create view v as select type from t;", ora),
+            "create view v as select type from t"
+        );
+        assert_eq!(
+            strip_terminator("begin null; end;\n/", ora),
+            "begin null; end;"
+        );
+        assert_eq!(
+            strip_terminator("select 1 from dual;", ora),
+            "select 1 from dual"
+        );
+    }
+
+    #[test]
+    fn finds_the_main_table() {
+        let pg = Dialect::Postgres;
+        assert_eq!(
+            main_table(
+                "select * from app.\"Orders\" o where o.id in (select 1 from x)",
+                pg
+            ),
+            Some("app.Orders".into())
+        );
+        assert_eq!(main_table("insert into t values (1)", pg), Some("t".into()));
+        assert_eq!(main_table("select 1", pg), None);
+    }
+}
@@ -0,0 +1,435 @@
+use crate::database::Dialect;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+    String,
+    QuotedIdentifier,
+    Number,
+    Word,
+    BindVariable,
+    Operator,
+    OpenParen,
+    CloseParen,
+    Comma,
+    Semicolon,
+    Dot,
+    Other,
+}
+
+/// A token, borrowing its text from the tokenized statement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    /// Byte offset of the token in the statement
+    pub start: usize,
+    /// False for strings, quoted identifiers and comments missing their closing delimiter
+    pub terminated: bool,
+}
+
+impl<'a> Token<'a> {
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Word && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    /// Whitespace and comments
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self.kind,
+            TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment
+        )
+    }
+
+    /// The name of an identifier token, without quotes
+    pub fn identifier(&self) -> Option<String> {
+        match self.kind {
+            TokenKind::Word => Some(self.text.to_string()),
+            TokenKind::QuotedIdentifier if self.terminated && self.text.len() >= 2 => {
+                let q = &self.text[..1];
+                let inner = &self.text[1..self.text.len() - 1];
+                if q == "[" {
+                    Some(inner.to_string())
+                } else {
+                    Some(inner.replace(&q.repeat(2), q))
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+const OPERATOR_CHARS: &str = "+-*/<>=~!@#%^&|?:";
+
+struct Lexer<'a> {
+    sql: &'a str,
+    pos: usize,
+    dialect: Dialect,
+}
+
+impl<'a> Lexer<'a> {
+    fn rest(&self) -> &'a str {
+        &self.sql[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn prev(&self) -> Option<char> {
+        self.sql[..self.pos].chars().next_back()
+    }
+
+    /// Advances while the predicate holds, returns the new position
+    fn eat_while<F: Fn(char) -> bool>(&mut self, f: F) -> usize {
+        while let Some(c) = self.peek() {
+            if !f(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        self.pos
+    }
+
+    /// Advances behind the next occurrence of `end`, returns whether it was found
+    fn eat_until(&mut self, end: &str) -> bool {
+        match self.rest().find(end) {
+            Some(i) => {
+                self.pos += i + end.len();
+                true
+            }
+            None => {
+                self.pos = self.sql.len();
+                false
+            }
+        }
+    }
+
+    /// Reads a quoted string or identifier starting at the current quote
+    /// character; doubled quotes and, if allowed, backslashes escape
+    fn eat_quoted(&mut self, quote: char, backslash_escapes: bool) -> bool {
+        self.pos += quote.len_utf8();
+        while let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+            if backslash_escapes && c == '\\' {
+                if let Some(n) = self.peek() {
+                    self.pos += n.len_utf8();
+                }
+            } else if c == quote {
+                if self.peek() == Some(quote) {
+                    self.pos += quote.len_utf8();
+                } else {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn eat_block_comment(&mut self) -> bool {
+        self.pos += 2;
+        let mut depth = 1;
+        while self.pos < self.sql.len() {
+            let rest = self.rest();
+            if rest.starts_with("*/") {
+                self.pos += 2;
+                depth -= 1;
+                if depth == 0 {
+                    return true;
+                }
+            } else if rest.starts_with("/*") && self.dialect == Dialect::Postgres {
+                self.pos += 2;
+                depth += 1;
+            } else {
+                self.pos += rest.chars().next().unwrap().len_utf8();
+            }
+        }
+        false
+    }
+
+    /// Postgres `$tag$...$tag$` strings; returns None if this is no dollar quote
+    fn eat_dollar_quoted(&mut self) -> Option<bool> {
+        let rest = self.rest();
+        let body = &rest[1..];
+        let tag_len = body
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(body.len());
+        if !body[tag_len..].starts_with('$') || body.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        let tag = &rest[..tag_len + 2];
+        self.pos += tag.len();
+        Some(self.eat_until(tag))
+    }
+
+    /// Oracle `q'[...]'` strings, starting at the `'`
+    fn eat_oracle_q_quote(&mut self) -> bool {
+        let open = match self.peek_nth(1) {
+            Some(c) => c,
+            None => {
+                self.pos = self.sql.len();
+                return false;
+            }
+        };
+        let close = match open {
+            '[' => ']',
+            '{' => '}',
+            '<' => '>',
+            '(' => ')',
+            c => c,
+        };
+        self.pos += 1 + open.len_utf8();
+        let end = format!("{}'", close);
+        self.eat_until(&end)
+    }
+
+    fn next_token(&mut self) -> Option<Token<'a>> {
+        let start = self.pos;
+        let c = self.peek()?;
+        let mut terminated = true;
+        let rest = self.rest();
+
+        let kind = if c.is_whitespace() {
+            self.eat_while(char::is_whitespace);
+            TokenKind::Whitespace
+        } else if rest.starts_with("--") || (c == '#' && self.dialect == Dialect::Mysql) {
+            self.eat_while(|c| c != '\n');
+            TokenKind::LineComment
+        } else if rest.starts_with("/*") {
+            terminated = self.eat_block_comment();
+            TokenKind::BlockComment
+        } else if c == '\'' {
+            terminated = self.eat_quoted('\'', self.dialect == Dialect::Mysql);
+            TokenKind::String
+        } else if c == '"' {
+            if self.dialect == Dialect::Mysql {
+                terminated = self.eat_quoted('"', true);
+                TokenKind::String
+            } else {
+                terminated = self.eat_quoted('"', false);
+                TokenKind::QuotedIdentifier
+            }
+        } else if c == '`' && matches!(self.dialect, Dialect::Mysql | Dialect::Sqlite) {
+            terminated = self.eat_quoted('`', false);
+            TokenKind::QuotedIdentifier
+        } else if c == '[' && self.dialect == Dialect::Sqlite {
+            terminated = self.eat_until("]");
+            TokenKind::QuotedIdentifier
+        } else if c == '$' && self.dialect == Dialect::Postgres {
+            if self
+                .peek_nth(1)
+                .map(|c| c.is_ascii_digit())
+                .unwrap_or(false)
+            {
+                self.pos += 1;
+                self.eat_while(|c| c.is_ascii_digit());
+                TokenKind::BindVariable
+            } else if let Some(t) = self.eat_dollar_quoted() {
+                terminated = t;
+                TokenKind::String
+            } else {
+                self.pos += 1;
+                TokenKind::Other
+            }
+        } else if c.is_ascii_digit()
+            || (c == '.'
+                && self
+                    .peek_nth(1)
+                    .map(|c| c.is_ascii_digit())
+                    .unwrap_or(false))
+        {
+            self.eat_while(|c| c.is_ascii_digit() || c == '.');
+            if matches!(self.peek(), Some('e') | Some('E'))
+                && self
+                    .peek_nth(1)
+                    .map(|c| c.is_ascii_digit() || c == '+' || c == '-')
+                    .unwrap_or(false)
+            {
+                self.pos += 2;
+                self.eat_while(|c| c.is_ascii_digit());
+            }
+            TokenKind::Number
+        } else if c.is_alphabetic() || c == '_' {
+            let word_end =
+                self.eat_while(|c| c.is_alphanumeric() || c == '_' || c == '$' || c == '#');
+            let word = &self.sql[start..word_end];
+            // Prefixed strings like E'..', N'..', X'..' and Oracle's q'[..]'
+            if self.peek() == Some('\'') && word.len() == 1 {
+                match word {
+                    "q" | "Q" if self.dialect == Dialect::Oracle => {
+                        terminated = self.eat_oracle_q_quote();
+                        TokenKind::String
+                    }
+                    "e" | "E" => {
+                        terminated = self.eat_quoted('\'', true);
+                        TokenKind::String
+                    }
+                    "n" | "N" | "b" | "B" | "x" | "X" => {
+                        terminated = self.eat_quoted('\'', self.dialect == Dialect::Mysql);
+                        TokenKind::String
+                    }
+                    _ => TokenKind::Word,
+                }
+            } else {
+                TokenKind::Word
+            }
+        } else if c == ':'
+            && self.prev() != Some(':')
+            && self
+                .peek_nth(1)
+                .map(|c| c.is_alphanumeric() || c == '_')
+                .unwrap_or(false)
+        {
+            self.pos += 1;
+            self.eat_while(|c| c.is_alphanumeric() || c == '_');
+            TokenKind::BindVariable
+        } else if c == '?' && matches!(self.dialect, Dialect::Mysql | Dialect::Sqlite) {
+            self.pos += 1;
+            self.eat_while(|c| c.is_ascii_digit());
+            TokenKind::BindVariable
+        } else if c == '@' && matches!(self.dialect, Dialect::Mysql | Dialect::Sqlite) {
+            self.pos += 1;
+            self.eat_while(|c| c.is_alphanumeric() || c == '_' || c == '@' || c == '.');
+            TokenKind::BindVariable
+        } else if c == '(' {
+            self.pos += 1;
+            TokenKind::OpenParen
+        } else if c == ')' {
+            self.pos += 1;
+            TokenKind::CloseParen
+        } else if c == ',' {
+            self.pos += 1;
+            TokenKind::Comma
+        } else if c == ';' {
+            self.pos += 1;
+            TokenKind::Semicolon
+        } else if c == '.' {
+            self.pos += 1;
+            TokenKind::Dot
+        } else if OPERATOR_CHARS.contains(c) {
+            self.pos += 1;
+            while let Some(n) = self.peek() {
+                let r = self.rest();
+                if !OPERATOR_CHARS.contains(n) || r.starts_with("--") || r.starts_with("/*") {
+                    break;
+                }
+                self.pos += 1;
+            }
+            TokenKind::Operator
+        } else {
+            self.pos += c.len_utf8();
+            TokenKind::Other
+        };
+
+        Some(Token {
+            kind,
+            text: &self.sql[start..self.pos],
+            start,
+            terminated,
+        })
+    }
+}
+
+/// Splits a statement into tokens; concatenating their texts gives the original statement
+pub fn tokenize(sql: &str, dialect: Dialect) -> Vec<Token<'_>> {
+    let mut lexer = Lexer {
+        sql,
+        pos: 0,
+        dialect,
+    };
+    let mut tokens = vec![];
+    while let Some(t) = lexer.next_token() {
+        tokens.push(t);
+    }
+    tokens
+}
+
+/// The tokens without whitespace and comments
+pub fn significant_tokens(sql: &str, dialect: Dialect) -> Vec<Token<'_>> {
+    tokenize(sql, dialect)
+        .into_iter()
+        .filter(|t| !t.is_trivia())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(sql: &str, dialect: Dialect) -> Vec<(TokenKind, &str)> {
+        significant_tokens(sql, dialect)
+            .into_iter()
+            .map(|t| (t.kind, t.text))
+            .collect()
+    }
+
+    #[test]
+    fn concatenates_to_the_input() {
+        let sql = "select $a$x$a$, e'\\'', \"q\"\"i\" -- c\n/* b */ from t where a = :p;";
+        let text: String = tokenize(sql, Dialect::Postgres)
+            .iter()
+            .map(|t| t.text)
+            .collect();
+        assert_eq!(text, sql);
+    }
+
+    #[test]
+    fn reads_quotes_per_dialect() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("$tag$ a ' $ b $tag$ $1", Dialect::Postgres),
+            vec![(String, "$tag$ a ' $ b $tag$"), (BindVariable, "$1")]
+        );
+        assert_eq!(
+            kinds("'a\\'b' \"c\" `d`", Dialect::Mysql),
+            vec![
+                (String, "'a\\'b'"),
+                (String, "\"c\""),
+                (QuotedIdentifier, "`d`")
+            ]
+        );
+        assert_eq!(
+            kinds("q'{it's}' \"x\"", Dialect::Oracle),
+            vec![(String, "q'{it's}'"), (QuotedIdentifier, "\"x\"")]
+        );
+        assert_eq!(
+            kinds("[a b] ?1", Dialect::Sqlite),
+            vec![(QuotedIdentifier, "[a b]"), (BindVariable, "?1")]
+        );
+    }
+
+    #[test]
+    fn marks_unterminated_tokens() {
+        let t = tokenize("select 'abc", Dialect::Postgres);
+        assert!(!t.last().unwrap().terminated);
+        let t = tokenize("/* a /* b */", Dialect::Postgres);
+        assert!(!t[0].terminated);
+        let t = tokenize("/* a /* b */", Dialect::Oracle);
+        assert!(t[0].terminated);
+    }
+
+    #[test]
+    fn tells_casts_from_bind_variables() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("a::int = :b", Dialect::Postgres),
+            vec![
+                (Word, "a"),
+                (Operator, "::"),
+                (Word, "int"),
+                (Operator, "="),
+                (BindVariable, ":b")
+            ]
+        );
+    }
+}
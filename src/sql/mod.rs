@@ -0,0 +1,7 @@
+//! Dialect aware tokenizing and classification of SQL statements
+
+pub mod classify;
+pub mod lexer;
+
+pub use classify::{classify, split_statements, Classification, StatementKind};
+pub use lexer::{tokenize, Token, TokenKind};
@@ -0,0 +1,370 @@
+//! Context-aware completion of SQL: which candidates fit depends on the
+//! clause the cursor is in and on the tables the statement refers to.
+
+use super::schema::SchemaCache;
+use crate::database::{Dialect, TableRef};
+use crate::sql::{tokenize, Token, TokenKind};
+
+/// Keywords offered at the start of a statement
+const STATEMENT_KEYWORDS: &[&str] = &[
+    "select", "insert", "update", "delete", "with", "create", "alter", "drop", "truncate",
+    "explain", "commit", "rollback", "grant", "revoke", "merge",
+];
+
+/// Keywords offered inside statements
+const KEYWORDS: &[&str] = &[
+    "select",
+    "from",
+    "where",
+    "and",
+    "or",
+    "not",
+    "in",
+    "is",
+    "null",
+    "like",
+    "between",
+    "exists",
+    "join",
+    "inner",
+    "left",
+    "right",
+    "full",
+    "outer",
+    "cross",
+    "on",
+    "using",
+    "as",
+    "group",
+    "order",
+    "by",
+    "having",
+    "limit",
+    "offset",
+    "union",
+    "all",
+    "distinct",
+    "case",
+    "when",
+    "then",
+    "else",
+    "end",
+    "asc",
+    "desc",
+    "set",
+    "values",
+    "into",
+    "returning",
+];
+
+/// Words ending a table reference, so that they are not taken for an alias
+const NOT_ALIAS: &[&str] = &[
+    "where",
+    "join",
+    "inner",
+    "left",
+    "right",
+    "full",
+    "outer",
+    "cross",
+    "natural",
+    "on",
+    "using",
+    "group",
+    "order",
+    "having",
+    "limit",
+    "offset",
+    "union",
+    "except",
+    "intersect",
+    "minus",
+    "set",
+    "values",
+    "returning",
+    "fetch",
+    "for",
+    "window",
+    "lateral",
+    "select",
+    "default",
+];
+
+/// Keywords after which a table name follows
+const TABLE_KEYWORDS: &[&str] = &["from", "join", "into", "update", "table"];
+
+/// Keywords starting a clause that refers to columns
+const COLUMN_KEYWORDS: &[&str] = &[
+    "select",
+    "where",
+    "on",
+    "and",
+    "or",
+    "not",
+    "by",
+    "having",
+    "set",
+    "when",
+    "then",
+    "else",
+    "case",
+    "returning",
+    "distinct",
+];
+
+const FUNCTIONS: &[&str] = &[
+    "count", "sum", "avg", "min", "max", "coalesce", "nullif", "upper", "lower", "trim", "length",
+    "substr", "replace", "round", "abs", "cast",
+];
+
+fn dialect_functions(dialect: Dialect) -> &'static [&'static str] {
+    match dialect {
+        Dialect::Postgres => &[
+            "now",
+            "date_trunc",
+            "to_char",
+            "to_date",
+            "string_agg",
+            "array_agg",
+            "extract",
+            "generate_series",
+            "jsonb_build_object",
+            "greatest",
+            "least",
+        ],
+        Dialect::Oracle => &[
+            "nvl",
+            "decode",
+            "to_char",
+            "to_date",
+            "to_number",
+            "trunc",
+            "listagg",
+            "instr",
+            "sysdate",
+            "greatest",
+            "least",
+        ],
+        Dialect::Mysql => &[
+            "ifnull",
+            "concat",
+            "now",
+            "date_format",
+            "str_to_date",
+            "group_concat",
+            "greatest",
+            "least",
+        ],
+        Dialect::Sqlite => &[
+            "ifnull",
+            "group_concat",
+            "date",
+            "datetime",
+            "strftime",
+            "printf",
+            "instr",
+        ],
+    }
+}
+
+/// A table the statement refers to, with its alias
+#[derive(Debug)]
+struct ScopeTable {
+    table: TableRef,
+    alias: Option<String>,
+}
+
+fn is_identifier(t: &Token) -> bool {
+    matches!(t.kind, TokenKind::Word | TokenKind::QuotedIdentifier)
+}
+
+fn is_one_of(t: &Token, keywords: &[&str]) -> bool {
+    keywords.iter().any(|k| t.is_keyword(k))
+}
+
+/// The tables after FROM, JOIN, UPDATE and INTO, `[schema.]table [[AS] alias]`
+fn tables_in_scope(tokens: &[Token]) -> Vec<ScopeTable> {
+    let mut res = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let is_from = tokens[i].is_keyword("from");
+        if !is_one_of(&tokens[i], &["from", "join", "update", "into"]) {
+            i += 1;
+            continue;
+        }
+        i += 1;
+        loop {
+            let (schema, name) = match (tokens.get(i), tokens.get(i + 1), tokens.get(i + 2)) {
+                (Some(s), Some(d), Some(n))
+                    if is_identifier(s) && d.kind == TokenKind::Dot && is_identifier(n) =>
+                {
+                    i += 3;
+                    (s.identifier().unwrap(), n.identifier().unwrap())
+                }
+                (Some(n), _, _) if is_identifier(n) && !is_one_of(n, NOT_ALIAS) => {
+                    i += 1;
+                    (String::new(), n.identifier().unwrap())
+                }
+                _ => break,
+            };
+            if tokens.get(i).map(|t| t.is_keyword("as")).unwrap_or(false) {
+                i += 1;
+            }
+            let alias = match tokens.get(i) {
+                Some(a) if is_identifier(a) && !is_one_of(a, NOT_ALIAS) => {
+                    i += 1;
+                    a.identifier()
+                }
+                _ => None,
+            };
+            res.push(ScopeTable {
+                table: TableRef { schema, name },
+                alias,
+            });
+            match tokens.get(i) {
+                Some(t) if is_from && t.kind == TokenKind::Comma => i += 1,
+                _ => break,
+            }
+        }
+    }
+    res
+}
+
+/// Writes keywords in upper case if the user started the word in upper case
+fn keyword_case(keyword: &str, prefix: &str) -> String {
+    if prefix
+        .chars()
+        .next()
+        .map(char::is_uppercase)
+        .unwrap_or(false)
+    {
+        keyword.to_uppercase()
+    } else {
+        keyword.to_string()
+    }
+}
+
+/// Returns where the completed word starts and the candidates for it
+pub fn complete_sql<F>(
+    line: &str,
+    pos: usize,
+    dialect: Dialect,
+    cache: &mut SchemaCache,
+    load_columns: &mut F,
+) -> (usize, Vec<String>)
+where
+    F: FnMut(&TableRef) -> Option<Vec<String>>,
+{
+    let tokens = tokenize(line, dialect);
+    // Nothing to complete inside strings and comments
+    if tokens.iter().any(|t| {
+        t.start < pos
+            && (pos < t.end() || !t.terminated)
+            && matches!(
+                t.kind,
+                TokenKind::String | TokenKind::LineComment | TokenKind::BlockComment
+            )
+    }) {
+        return (pos, vec![]);
+    }
+
+    // The statement around the cursor
+    let start_of_statement = tokens
+        .iter()
+        .rev()
+        .find(|t| t.kind == TokenKind::Semicolon && t.end() <= pos)
+        .map(|t| t.end())
+        .unwrap_or(0);
+    let end_of_statement = tokens
+        .iter()
+        .find(|t| t.kind == TokenKind::Semicolon && t.start >= pos)
+        .map(|t| t.start)
+        .unwrap_or(line.len());
+    let statement: Vec<Token> = tokens
+        .iter()
+        .filter(|t| !t.is_trivia() && t.start >= start_of_statement && t.end() <= end_of_statement)
+        .cloned()
+        .collect();
+
+    let (start, prefix) = match statement
+        .iter()
+        .find(|t| t.kind == TokenKind::Word && t.start < pos && pos <= t.end())
+    {
+        Some(t) => (t.start, &line[t.start..pos]),
+        None => (pos, ""),
+    };
+    let before: Vec<&Token> = statement.iter().filter(|t| t.end() <= start).collect();
+    let scope = tables_in_scope(&statement);
+
+    let mut candidates: Vec<String> = vec![];
+    let n = before.len();
+    if n >= 2 && before[n - 1].kind == TokenKind::Dot && is_identifier(before[n - 2]) {
+        // `alias.` or `table.` gives columns, `schema.` tables
+        let qualifier = before[n - 2].identifier().unwrap();
+        let table = scope.iter().find(|s| {
+            s.alias
+                .as_ref()
+                .map(|a| a.eq_ignore_ascii_case(&qualifier))
+                .unwrap_or(false)
+                || s.table.name.eq_ignore_ascii_case(&qualifier)
+        });
+        match table {
+            Some(s) => candidates.extend(cache.columns(&s.table, load_columns).iter().cloned()),
+            None => candidates.extend(
+                cache
+                    .tables()
+                    .iter()
+                    .filter(|t| t.schema.eq_ignore_ascii_case(&qualifier))
+                    .map(|t| t.name.clone()),
+            ),
+        }
+    } else if n == 0 {
+        candidates.extend(STATEMENT_KEYWORDS.iter().map(|k| keyword_case(k, prefix)));
+    } else {
+        let clause = before
+            .iter()
+            .rev()
+            .find(|t| is_one_of(t, TABLE_KEYWORDS) || is_one_of(t, COLUMN_KEYWORDS));
+        let previous = before[n - 1];
+        let wants_table = is_one_of(previous, TABLE_KEYWORDS)
+            || (previous.kind == TokenKind::Comma
+                && clause.map(|c| c.is_keyword("from")).unwrap_or(false));
+        if wants_table {
+            candidates.extend(cache.tables().iter().map(|t| t.name.clone()));
+            let mut schemas: Vec<String> = cache
+                .tables()
+                .iter()
+                .filter(|t| !t.schema.is_empty())
+                .map(|t| format!("{}.", t.schema))
+                .collect();
+            schemas.sort();
+            schemas.dedup();
+            candidates.extend(schemas);
+        } else {
+            if clause
+                .map(|c| is_one_of(c, COLUMN_KEYWORDS))
+                .unwrap_or(false)
+            {
+                for s in &scope {
+                    candidates.extend(cache.columns(&s.table, load_columns).iter().cloned());
+                }
+                candidates.extend(
+                    FUNCTIONS
+                        .iter()
+                        .chain(dialect_functions(dialect))
+                        .map(|f| format!("{}(", keyword_case(f, prefix))),
+                );
+            }
+            candidates.extend(KEYWORDS.iter().map(|k| keyword_case(k, prefix)));
+        }
+    }
+
+    let lower = prefix.to_lowercase();
+    let mut seen = vec![];
+    for c in candidates {
+        if c.to_lowercase().starts_with(&lower) && !seen.contains(&c) {
+            seen.push(c);
+        }
+    }
+    (start, seen)
+}
@@ -0,0 +1,245 @@
+//! Syntax highlighting of the input line. The line is tokenized with the SQL
+//! lexer and every token is colored on its own, so the text, and with it the
+//! cursor position, stays exactly as typed.
+
+use super::theme::Role;
+use crate::database::Dialect;
+use crate::sql::{tokenize, Token, TokenKind};
+
+/// Keywords of all dialects
+const KEYWORDS: &[&str] = &[
+    "add",
+    "all",
+    "alter",
+    "and",
+    "any",
+    "as",
+    "asc",
+    "begin",
+    "between",
+    "by",
+    "case",
+    "cascade",
+    "check",
+    "column",
+    "commit",
+    "constraint",
+    "create",
+    "cross",
+    "current_date",
+    "current_timestamp",
+    "default",
+    "delete",
+    "desc",
+    "distinct",
+    "drop",
+    "else",
+    "end",
+    "except",
+    "exists",
+    "explain",
+    "false",
+    "fetch",
+    "first",
+    "for",
+    "foreign",
+    "from",
+    "full",
+    "function",
+    "grant",
+    "group",
+    "having",
+    "if",
+    "in",
+    "index",
+    "inner",
+    "insert",
+    "intersect",
+    "into",
+    "is",
+    "join",
+    "key",
+    "left",
+    "like",
+    "limit",
+    "merge",
+    "natural",
+    "next",
+    "not",
+    "null",
+    "offset",
+    "on",
+    "only",
+    "or",
+    "order",
+    "outer",
+    "over",
+    "partition",
+    "primary",
+    "procedure",
+    "references",
+    "replace",
+    "revoke",
+    "right",
+    "rollback",
+    "row",
+    "rows",
+    "savepoint",
+    "schema",
+    "select",
+    "sequence",
+    "set",
+    "some",
+    "table",
+    "then",
+    "to",
+    "transaction",
+    "trigger",
+    "true",
+    "truncate",
+    "union",
+    "unique",
+    "update",
+    "using",
+    "values",
+    "view",
+    "when",
+    "where",
+    "window",
+    "with",
+];
+
+fn dialect_keywords(dialect: Dialect) -> &'static [&'static str] {
+    match dialect {
+        Dialect::Postgres => &[
+            "analyze",
+            "conflict",
+            "do",
+            "ilike",
+            "lateral",
+            "materialized",
+            "nothing",
+            "returning",
+            "show",
+            "similar",
+            "vacuum",
+        ],
+        Dialect::Oracle => &[
+            "connect",
+            "declare",
+            "exception",
+            "loop",
+            "minus",
+            "package",
+            "prior",
+            "rownum",
+            "start",
+            "synonym",
+            "sysdate",
+        ],
+        Dialect::Mysql => &[
+            "auto_increment",
+            "database",
+            "describe",
+            "duplicate",
+            "engine",
+            "ignore",
+            "regexp",
+            "show",
+            "straight_join",
+            "use",
+        ],
+        Dialect::Sqlite => &[
+            "attach",
+            "autoincrement",
+            "detach",
+            "glob",
+            "pragma",
+            "returning",
+            "rowid",
+            "vacuum",
+            "without",
+        ],
+    }
+}
+
+fn is_keyword(word: &str, dialect: Dialect) -> bool {
+    KEYWORDS
+        .iter()
+        .chain(dialect_keywords(dialect))
+        .any(|k| word.eq_ignore_ascii_case(k))
+}
+
+/// Indexes of the parentheses without a partner
+fn unbalanced_parens(tokens: &[Token]) -> Vec<usize> {
+    let mut open = vec![];
+    let mut unbalanced = vec![];
+    for (i, t) in tokens.iter().enumerate() {
+        match t.kind {
+            TokenKind::OpenParen => open.push(i),
+            TokenKind::CloseParen if open.pop().is_none() => unbalanced.push(i),
+            _ => {}
+        }
+    }
+    unbalanced.extend(open);
+    unbalanced
+}
+
+/// Colors a statement token by token
+pub fn highlight_sql(sql: &str, dialect: Dialect) -> String {
+    let tokens = tokenize(sql, dialect);
+    let unbalanced = unbalanced_parens(&tokens);
+    let mut res = String::with_capacity(sql.len() * 2);
+    for (i, t) in tokens.iter().enumerate() {
+        // Words behind a dot are names even if they look like keywords, as in `t.order`
+        let qualified = i > 0 && tokens[i - 1].kind == TokenKind::Dot;
+        let colored = match t.kind {
+            _ if !t.terminated => Role::Invalid.paint(t.text),
+            _ if unbalanced.contains(&i) => Role::Invalid.paint(t.text),
+            TokenKind::Word if !qualified && is_keyword(t.text, dialect) => {
+                Role::Keyword.paint(t.text)
+            }
+            TokenKind::Word | TokenKind::QuotedIdentifier => Role::Name.paint(t.text),
+            TokenKind::String => Role::String.paint(t.text),
+            TokenKind::Number => Role::Number.paint(t.text),
+            TokenKind::LineComment | TokenKind::BlockComment => Role::Comment.paint(t.text),
+            TokenKind::BindVariable => Role::BindVariable.paint(t.text),
+            TokenKind::Operator => Role::Operator.paint(t.text),
+            _ => {
+                res.push_str(t.text);
+                continue;
+            }
+        };
+        res.push_str(&colored.to_string());
+    }
+    res
+}
+
+/// Colors an input line: `:` commands and `@` queries get their name colored,
+/// everything else is SQL
+pub fn highlight_line(line: &str, dialect: Dialect) -> String {
+    let trimmed = line.trim_start();
+    if !(trimmed.starts_with(':') || trimmed.starts_with('@')) {
+        return highlight_sql(line, dialect);
+    }
+    let name_end = trimmed
+        .find(char::is_whitespace)
+        .map(|i| i + line.len() - trimmed.len())
+        .unwrap_or(line.len());
+    let (name, rest) = line.split_at(name_end);
+    let rest = match name.trim_start() {
+        ":explain" => highlight_sql(rest, dialect),
+        // `:fanout <group> <statement>`
+        ":fanout" => {
+            let trimmed = rest.trim_start();
+            let group_end = trimmed
+                .find(char::is_whitespace)
+                .map(|i| i + rest.len() - trimmed.len())
+                .unwrap_or(rest.len());
+            let (group, sql) = rest.split_at(group_end);
+            format!("{}{}", group, highlight_sql(sql, dialect))
+        }
+        _ => rest.to_string(),
+    };
+    format!("{}{}", Role::Command.paint(name), rest)
+}
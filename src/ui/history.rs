@@ -0,0 +1,170 @@
+//! The input history of each connection, kept in `~/.dbc/history/<identifier>.jsonl`.
+//! Every line is appended as soon as it was executed, so nothing is lost if dbc dies.
+//! A connection without a history starts with the lines of `~/.dbc_history`, which all
+//! connections shared before.
+
+use super::ExecutionSummary;
+use anyhow::Context;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// RFC 3339 time of execution
+    pub time: String,
+    pub line: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u64>,
+    /// Why the statement failed or was not executed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    /// An entry for a line executed just now; commands without statements have no summary
+    pub fn new(line: &str, summary: Option<ExecutionSummary>) -> HistoryEntry {
+        HistoryEntry {
+            time: Local::now().to_rfc3339(),
+            line: line.to_string(),
+            duration_ms: summary.as_ref().map(|s| s.duration.as_millis() as u64),
+            rows: summary.as_ref().map(|s| s.rows),
+            error: summary.and_then(|s| s.error),
+        }
+    }
+}
+
+pub struct History {
+    path: PathBuf,
+    entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Reads the history of a connection; unreadable lines are skipped
+    pub fn open(identifier: &str) -> Result<History> {
+        let home = home_dir().unwrap();
+        let path = home
+            .join(".dbc")
+            .join("history")
+            .join(super::file_name(identifier, "jsonl"));
+        if !path.exists() {
+            import_legacy(&home.join(".dbc_history"), &path)?;
+        }
+
+        let mut entries = vec![];
+        if path.exists() {
+            let file =
+                File::open(&path).with_context(|| format!("Cannot open history {:?}", path))?;
+            for line in BufReader::new(file).lines() {
+                if let Ok(e) = serde_json::from_str(&line?) {
+                    entries.push(e);
+                }
+            }
+        }
+        Ok(History { path, entries })
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /// The entry with the number shown by `:history`, counted from 1
+    pub fn get(&self, n: usize) -> Option<&HistoryEntry> {
+        n.checked_sub(1).and_then(|i| self.entries.get(i))
+    }
+
+    /// Adds an entry and writes it to the file right away
+    pub fn append(&mut self, entry: HistoryEntry) -> Result<()> {
+        if let Some(d) = self.path.parent() {
+            fs::create_dir_all(d)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Cannot write history {:?}", self.path))?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+}
+
+/// Writes the lines of the shared rustyline history into the history of a connection,
+/// with the time the shared one was last written
+fn import_legacy(legacy: &Path, path: &Path) -> Result<()> {
+    let (content, modified) = match (fs::read_to_string(legacy), fs::metadata(legacy)) {
+        (Ok(c), Ok(m)) => (c, m.modified()?),
+        _ => return Ok(()),
+    };
+    let time = DateTime::<Local>::from(modified).to_rfc3339();
+    if let Some(d) = path.parent() {
+        fs::create_dir_all(d)?;
+    }
+    let mut file =
+        File::create(path).with_context(|| format!("Cannot write history {:?}", path))?;
+    for line in legacy_lines(&content) {
+        let entry = HistoryEntry {
+            time: time.clone(),
+            line,
+            duration_ms: None,
+            rows: None,
+            error: None,
+        };
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+    }
+    Ok(())
+}
+
+/// The lines of a rustyline history file; from version 2 on, newlines and backslashes
+/// are escaped
+fn legacy_lines(content: &str) -> Vec<String> {
+    let mut lines = content.lines().peekable();
+    let v2 = lines.next_if_eq(&"#V2").is_some();
+    lines
+        .filter(|l| !l.is_empty())
+        .map(|l| {
+            if !v2 {
+                return l.to_string();
+            }
+            let mut line = String::with_capacity(l.len());
+            let mut chars = l.chars();
+            while let Some(c) = chars.next() {
+                match (c, chars.clone().next()) {
+                    ('\\', Some('n')) => {
+                        line.push('\n');
+                        chars.next();
+                    }
+                    ('\\', Some('\\')) => {
+                        line.push('\\');
+                        chars.next();
+                    }
+                    _ => line.push(c),
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_legacy_lines() {
+        assert_eq!(
+            legacy_lines("select 1;\n\nselect 2;\n"),
+            ["select 1;", "select 2;"]
+        );
+        assert_eq!(
+            legacy_lines("#V2\nselect 1\\n from dual;\nselect '\\\\';\n"),
+            ["select 1\n from dual;", "select '\\';"]
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! Ctrl-C handling: while a statement runs, SIGINT cancels it on the server.
+//! At the prompt, rustyline reads Ctrl-C as a key and no signal is raised.
+//! Anywhere else it is only noted, for prompts to treat it as a "no".
+
+use super::theme::Role;
+use crate::database::CancelHandle;
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+static RUNNING: Mutex<Option<CancelHandle>> = Mutex::new(None);
+/// Set by Ctrl-C outside of a running statement
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the SIGINT handler. It never quits dbc, which would skip writing the
+/// history and restoring the terminal.
+pub fn install() -> Result<()> {
+    ctrlc::set_handler(|| match RUNNING.lock().unwrap().as_ref() {
+        Some(cancel) => {
+            eprintln!("{}", Role::Warning.paint("Cancelling statement..."));
+            if let Err(e) = cancel() {
+                eprintln!("{}: {:#}", Role::Error.paint("Cannot cancel statement"), e);
+            }
+        }
+        None => INTERRUPTED.store(true, Ordering::SeqCst),
+    })?;
+    Ok(())
+}
+
+/// Whether Ctrl-C was pressed outside of a statement since the last call
+pub fn interrupted() -> bool {
+    INTERRUPTED.swap(false, Ordering::SeqCst)
+}
+
+/// Makes Ctrl-C cancel the running statement until dropped
+pub struct CancelGuard;
+
+impl CancelGuard {
+    pub fn new(handle: CancelHandle) -> CancelGuard {
+        *RUNNING.lock().unwrap() = Some(handle);
+        CancelGuard
+    }
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        *RUNNING.lock().unwrap() = None;
+    }
+}
@@ -0,0 +1,50 @@
+//! Fitting results into the terminal: how wide it is, how long values are cut
+//! or wrapped, and how wide a table gets.
+
+use std::borrow::Cow;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// The marker at the end of cut values
+const ELLIPSIS: char = '…';
+
+/// The width of the terminal, `None` if the output is no terminal
+pub fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(w, _)| w.0 as usize)
+}
+
+/// The display width of a value, that of its widest line
+pub fn text_width(s: &str) -> usize {
+    s.lines().map(UnicodeWidthStr::width).max().unwrap_or(0)
+}
+
+/// Makes a value at most `width` columns wide, either wrapped at word boundaries
+/// or cut after its first line that is too long
+pub fn fit(s: &str, width: Option<usize>, wrap: bool) -> Cow<'_, str> {
+    let width = match width {
+        Some(w) if text_width(s) > w => w.max(1),
+        _ => return Cow::Borrowed(s),
+    };
+    if wrap {
+        return Cow::Owned(textwrap::fill(s, width));
+    }
+    let mut res = String::new();
+    for line in s.lines() {
+        if line.width() <= width {
+            res.push_str(line);
+            res.push('\n');
+            continue;
+        }
+        let mut used = 0;
+        for c in line.chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w + 1 > width {
+                break;
+            }
+            res.push(c);
+            used += w;
+        }
+        res.push(ELLIPSIS);
+        break;
+    }
+    Cow::Owned(res)
+}
@@ -1,27 +1,136 @@
-use colored::Colorize;
-use rustyline::completion::extract_word;
+pub mod completion;
+pub mod highlight;
+pub mod history;
+pub mod interrupt;
+pub mod layout;
+pub mod pager;
+pub mod prompt;
+pub mod render;
+pub mod schema;
+pub mod session;
+pub mod theme;
+
+use crate::database::{Connection, Dialect, SessionInfo, TableRef};
+use colored::{Color, Colorize};
 use rustyline::completion::Completer;
 use rustyline::highlight::Highlighter;
 use rustyline::hint::Hinter;
 use rustyline::{Context, Result};
 use rustyline_derive::{Helper, Validator};
+use schema::SchemaCache;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
 use structopt::StructOpt;
+use theme::Role;
+
+/// How query results are printed, see `render`
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Lines drawn with ASCII characters
+    #[default]
+    #[serde(alias = "ascii")]
+    Table,
+    /// Lines drawn with box-drawing characters
+    Unicode,
+    /// Aligned columns without lines
+    Plain,
+    Markdown,
+    Html,
+    Latex,
+    /// Separated by `;`
+    Csv,
+    Tsv,
+    /// An array of objects, one per row
+    Json,
+}
+
+/// Where results that do not fit on the screen are shown
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pager {
+    /// The built-in grid, `$PAGER` if it cannot be used
+    #[default]
+    Auto,
+    /// `$PAGER`, `less -S` if it is not set
+    External,
+    Off,
+}
+
+/// When rows are printed vertically, one `column | value` line per column
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Expanded {
+    On,
+    Off,
+    /// For single rows and tables wider than the terminal
+    #[default]
+    Auto,
+}
 
 #[derive(Debug, Clone)]
 pub struct DbcClientOptions {
     pub row_limit: usize,
+    /// Columns shown at most, 0 for as many as fit into the terminal
     pub column_limit: usize,
+    pub format: OutputFormat,
+    pub expanded: Expanded,
+    /// Values wider are wrapped or cut
+    pub max_width: Option<usize>,
+    /// Wrap long values instead of cutting them
+    pub wrap: bool,
+    pub pager: Pager,
+    pub autocommit: bool,
+    pub read_only: bool,
+    /// Ask before executing DROP, TRUNCATE and UPDATE/DELETE without WHERE
+    pub confirm_writes: bool,
+    /// The connection is tagged as production
+    pub production: bool,
+    pub timezone: Option<String>,
+    pub null_string: String,
+    /// Overrides the identifier color of the theme in the prompt
+    pub prompt_color: Option<Color>,
+    /// Template of the prompt, see `prompt::render`
+    pub prompt: Option<String>,
+    pub theme: String,
+    pub server_output: bool,
+    pub timeout: Option<Duration>,
+    pub timing: bool,
+    /// How long the schema cache on disk is used before it is reloaded
+    pub schema_ttl: Option<Duration>,
 }
 
-impl DbcClientOptions {
-    pub fn default() -> DbcClientOptions {
+impl Default for DbcClientOptions {
+    fn default() -> DbcClientOptions {
         DbcClientOptions {
             row_limit: 20,
-            column_limit: 10,
+            column_limit: 0,
+            format: OutputFormat::Table,
+            expanded: Expanded::Auto,
+            max_width: None,
+            wrap: true,
+            pager: Pager::Auto,
+            autocommit: true,
+            read_only: false,
+            confirm_writes: false,
+            production: false,
+            timezone: None,
+            null_string: "NULL".into(),
+            prompt_color: None,
+            prompt: None,
+            theme: "default".into(),
+            server_output: false,
+            timeout: None,
+            timing: false,
+            schema_ttl: Some(Duration::from_secs(24 * 3600)),
         }
     }
+}
 
+impl DbcClientOptions {
     pub fn set_row_limit(&mut self, n: usize) {
         self.row_limit = n;
     }
@@ -30,22 +139,101 @@ impl DbcClientOptions {
     }
 }
 
-#[derive(Debug, Clone)]
+/// A file name for data kept per connection
+pub(crate) fn file_name(identifier: &str, extension: &str) -> String {
+    let name: String = identifier
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.{}", name, extension)
+}
+
+/// What the statements of one input line did, recorded in the history
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionSummary {
+    pub duration: Duration,
+    pub rows: u64,
+    /// Why a statement failed or was not executed
+    pub error: Option<String>,
+    /// A statement changed the schema
+    pub ddl: bool,
+    /// A statement may have changed the session context or the transaction state
+    pub session_changed: bool,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct DbcClient {
+    /// The name given with `:connect <identifier> as <name>`, shown instead of the identifier
+    pub name: Option<String>,
     pub last_select: Option<String>,
+    /// Summary of the last statements executed, taken by the history
+    pub last_execution: Option<ExecutionSummary>,
+    /// The last line that executed statements, for the prompt
+    pub last_status: Option<ExecutionSummary>,
+    /// Only kept up to date if the prompt shows it
+    pub session: SessionInfo,
     pub options: DbcClientOptions,
 }
 
 impl DbcClient {
-    pub fn default() -> Self {
-        DbcClient {
-            last_select: None,
-            options: DbcClientOptions::default(),
+    pub fn set_last_select(&mut self, query: &str) {
+        self.last_select = Some(String::from(query))
+    }
+
+    pub fn prompt(&self, conn: &dyn Connection) -> String {
+        let name = self.name.as_deref().unwrap_or_else(|| conn.identifier());
+        if let Some(template) = &self.options.prompt {
+            let ctx = prompt::PromptContext {
+                identifier: name,
+                dialect: conn.dialect(),
+                session: &self.session,
+                last: self.last_status.as_ref(),
+                identifier_color: self.options.prompt_color,
+            };
+            return prompt::render(template, &ctx, self.options.production);
         }
+        if self.options.production {
+            let p = format!("{} ({})>", name, conn.dialect().short_name());
+            return format!("{} ", Role::Production.paint(p));
+        }
+        let identifier = match self.options.prompt_color {
+            Some(c) => name.color(c),
+            None => Role::Identifier.paint(name),
+        };
+        format!(
+            "{} {}{} ",
+            identifier,
+            Role::Backend.paint(format!("({})", conn.dialect().short_name())),
+            ">"
+        )
     }
 
-    pub fn set_last_select(&mut self, query: &str) {
-        self.last_select = Some(String::from(query))
+    /// Remembers the outcome of a line for the prompt and reloads the session info
+    /// if the line may have changed it
+    pub fn update_prompt(&mut self, conn: &mut dyn Connection, summary: Option<&ExecutionSummary>) {
+        let summary = match summary {
+            Some(s) => s,
+            None => return,
+        };
+        self.last_status = Some(summary.clone());
+        let uses_session = self
+            .options
+            .prompt
+            .as_deref()
+            .map(prompt::uses_session)
+            .unwrap_or(false);
+        if summary.session_changed && uses_session {
+            // A failing query leaves the last known context in the prompt
+            if let Ok(info) = conn.session_info() {
+                self.session = info;
+            }
+        }
     }
 }
 
@@ -55,7 +243,7 @@ impl DbcClient {
 pub struct Opt {
     /// Database identifier
     #[structopt()]
-    pub identifier: String,
+    pub identifier: Option<String>,
 
     /// Quiet (do not print banners)
     #[structopt(short = "q")]
@@ -64,32 +252,78 @@ pub struct Opt {
     /// Cache the DB schema for completion
     #[structopt(long = "no-cache", parse(from_flag = std::ops::Not::not))]
     pub cache: bool,
+
+    #[structopt(subcommand)]
+    pub cmd: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Manage the encrypted password vault
+    Vault(VaultCommand),
+    /// Inspect the merged configuration
+    Config(ConfigCommand),
+    /// Execute a statement on all connections of a group (`-` reads it from stdin)
+    Fanout {
+        /// Output format, like json or markdown, instead of the configured one
+        #[structopt(short, long)]
+        format: Option<OutputFormat>,
+        group: String,
+        statement: String,
+    },
+    // Catches extra arguments, so that identifiers resembling a subcommand
+    // name are not rejected with a "did you mean" error
+    #[structopt(external_subcommand)]
+    Unknown(Vec<String>),
+}
+
+#[derive(StructOpt, Debug)]
+pub enum ConfigCommand {
+    /// List the config files and the connections defined in them
+    List,
+    /// Show the values of a connection and where they come from
+    Show { identifier: String },
+    /// Check all connections for errors
+    Validate,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum VaultCommand {
+    /// Store a password in the vault (prompts for it)
+    Set { name: String },
+    /// Remove a password from the vault
+    Remove { name: String },
+    /// List the names of the stored passwords
+    List,
 }
 
 #[derive(Helper, Validator)]
 pub struct Helper {
-    pub completions: Vec<String>,
+    /// Shared with the main loop, which does not use it while a line is edited
+    pub conn: Rc<RefCell<Box<dyn Connection>>>,
+    pub dialect: Dialect,
+    pub schema: Rc<RefCell<SchemaCache>>,
     pub query_completions: Vec<String>,
     pub command_completions: Vec<String>,
+    /// Names of the open connections, for `:use`
+    pub connection_names: Vec<String>,
+    /// Identifiers of the config, for `:connect`
+    pub identifiers: Vec<String>,
 }
 
-const KEYWORDS: &[&str] = &[
-    "select", "from", "where", "order", "group", "by", "set", "update", "insert", "values",
-    "delete", "and", "or",
-];
-
-fn is_keyword(v: &str) -> bool {
-    for k in KEYWORDS {
-        if *k == v {
-            return true;
-        }
+impl Helper {
+    /// Completes and highlights for another connection
+    pub fn activate(&mut self, session: &session::Session) {
+        self.conn = session.conn.clone();
+        self.dialect = session.dialect();
+        self.schema = session.schema.clone();
+        self.query_completions = session.query_completions.clone();
     }
-    return false;
 }
 
 impl Highlighter for Helper {
     fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
-        format!("{}", hint.blue()).into()
+        format!("{}", Role::Info.paint(hint)).into()
     }
 
     fn highlight_candidate<'c>(
@@ -105,18 +339,7 @@ impl Highlighter for Helper {
     }
 
     fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
-        let s: Vec<String> = line
-            .split_whitespace()
-            .map(|x| {
-                if is_keyword(x) {
-                    format!("{}", x.green())
-                } else {
-                    x.into()
-                }
-            })
-            .collect();
-
-        s.join(" ").into()
+        highlight::highlight_line(line, self.dialect).into()
     }
 }
 
@@ -124,10 +347,18 @@ impl Completer for Helper {
     type Candidate = String;
 
     fn complete(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<(usize, Vec<String>)> {
-        return Ok(complete(&self, line, pos, ctx));
+        Ok(complete(self, line, pos, ctx))
     }
 }
 
+fn matching(words: &[String], prefix: &str) -> Vec<String> {
+    words
+        .iter()
+        .filter(|w| w.starts_with(prefix))
+        .cloned()
+        .collect()
+}
+
 fn complete(helper: &Helper, line: &str, pos: usize, _ctx: &Context<'_>) -> (usize, Vec<String>) {
     if line.starts_with("@") {
         let words: Vec<String> = helper
@@ -137,6 +368,10 @@ fn complete(helper: &Helper, line: &str, pos: usize, _ctx: &Context<'_>) -> (usi
             .filter(|x| x.starts_with(&line[1..]))
             .collect();
         (1, words)
+    } else if let Some(prefix) = line.strip_prefix(":use ") {
+        (5, matching(&helper.connection_names, prefix))
+    } else if let Some(prefix) = line.strip_prefix(":connect ") {
+        (9, matching(&helper.identifiers, prefix))
     } else if line.starts_with(":") {
         let words: Vec<String> = helper
             .command_completions
@@ -146,15 +381,15 @@ fn complete(helper: &Helper, line: &str, pos: usize, _ctx: &Context<'_>) -> (usi
             .collect();
         (1, words)
     } else {
-        let (start, word) = extract_word(line, pos, None, |c| c == ' ');
-
-        let words: Vec<String> = helper
-            .completions
-            .clone()
-            .into_iter()
-            .filter(|x| x.starts_with(word))
-            .collect();
-        (start, words)
+        let mut conn = helper.conn.try_borrow_mut().ok();
+        let mut load = |t: &TableRef| conn.as_mut().and_then(|c| c.list_columns(t).ok());
+        completion::complete_sql(
+            line,
+            pos,
+            helper.dialect,
+            &mut helper.schema.borrow_mut(),
+            &mut load,
+        )
     }
 }
 
@@ -164,14 +399,14 @@ impl Hinter for Helper {
     fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<Self::Hint> {
         if line.starts_with("@") {
             let idx = pos - 1;
-            let (_start, words) = complete(&self, line, pos, ctx);
-            words.iter().nth(0).map(|x| String::from(&x[idx..]))
+            let (_start, words) = complete(self, line, pos, ctx);
+            words.first().map(|x| String::from(&x[idx..]))
         } else if pos > 5 {
-            let (start, words) = complete(&self, line, pos, ctx);
+            let (start, words) = complete(self, line, pos, ctx);
             let idx = pos - start;
 
             if idx > 3 {
-                words.iter().nth(0).map(|x| String::from(&x[idx..]))
+                words.first().and_then(|x| x.get(idx..)).map(String::from)
             } else {
                 None
             }
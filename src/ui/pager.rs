@@ -0,0 +1,577 @@
+//! A full-screen grid for results that do not fit on the screen. The column
+//! titles and the first column stay in place while scrolling; rows can be
+//! searched and sorted, and a cell copied to the clipboard of the terminal.
+
+use super::layout::{fit, text_width};
+use super::theme::Role;
+use super::{DbcClient, Pager};
+use crate::database::{QueryResult, Row};
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use prettytable::{Cell, Row as TableRow, Table};
+use std::cmp::Ordering;
+use std::env;
+use std::io::{stdout, Stdout, Write};
+use std::process::{Command, Stdio};
+
+/// Columns are not shown wider unless `max_width` says otherwise
+const MAX_COLUMN_WIDTH: usize = 40;
+
+const HELP: &str = "q quit  / search  n next  s sort  : row  c copy";
+
+/// Shows the rows in a pager if the output, `height` lines as printed, does not fit
+/// on the screen; returns false if the rows are to be printed as usual
+pub fn page(client: &DbcClient, res: &QueryResult, rows: &[Row], height: usize) -> Result<bool> {
+    let o = &client.options;
+    if o.pager == Pager::Off || !o.format.is_table() {
+        return Ok(false);
+    }
+    // Only if the output is a terminal
+    match terminal_size::terminal_size() {
+        // The prompt stays visible
+        Some((_, h)) if height >= h.0 as usize => {}
+        _ => return Ok(false),
+    }
+
+    if o.pager == Pager::Auto {
+        let max_width = o.max_width.unwrap_or(MAX_COLUMN_WIDTH);
+        let mut grid = Grid::new(res, rows, &o.null_string, max_width);
+        match grid.run() {
+            Ok(()) => return Ok(true),
+            // The grid needs a terminal it can control, like `$PAGER`
+            Err(_) if env::var("PAGER").is_ok() => {}
+            Err(_) => return Ok(false),
+        }
+    }
+    external(client, res, rows)?;
+    Ok(true)
+}
+
+/// Pipes the table into `$PAGER`
+fn external(client: &DbcClient, res: &QueryResult, rows: &[Row]) -> Result<()> {
+    let o = &client.options;
+    let mut table = Table::new();
+    table.set_format(o.format.table_format());
+    table.set_titles(TableRow::new(
+        res.columns.iter().map(|c| Cell::new(&c.name)).collect(),
+    ));
+    for r in rows {
+        table.add_row(TableRow::new(
+            r.data
+                .iter()
+                .map(|v| match v {
+                    Some(v) => Cell::new(&fit(v, o.max_width, o.wrap)),
+                    None => Cell::new(&o.null_string),
+                })
+                .collect(),
+        ));
+    }
+
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less -S".into());
+    let mut child = if cfg!(windows) {
+        Command::new("cmd")
+            .args(["/C", &pager])
+            .stdin(Stdio::piped())
+            .spawn()
+    } else {
+        Command::new("sh")
+            .args(["-c", &pager])
+            .stdin(Stdio::piped())
+            .spawn()
+    }
+    .with_context(|| format!("Cannot run pager {}", pager))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Fails when the pager is quit before reading everything
+        let _ = stdin.write_all(table.to_string().as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped
+struct Screen {
+    out: Stdout,
+}
+
+impl Screen {
+    fn enter() -> Result<Screen> {
+        terminal::enable_raw_mode()?;
+        let mut out = stdout();
+        if let Err(e) = execute!(out, EnterAlternateScreen, Hide) {
+            let _ = terminal::disable_raw_mode();
+            return Err(e.into());
+        }
+        Ok(Screen { out })
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, LeaveAlternateScreen, Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct Grid<'a> {
+    res: &'a QueryResult,
+    rows: &'a [Row],
+    null_string: &'a str,
+    widths: Vec<usize>,
+    /// Indexes into `rows` in the order shown
+    order: Vec<usize>,
+    /// The sorted column and whether the order is descending
+    sort: Option<(usize, bool)>,
+    /// The selected cell, `row` counted in `order`
+    row: usize,
+    col: usize,
+    /// The first row shown and the first column shown after the first one
+    top: usize,
+    left: usize,
+    search: Option<String>,
+    /// A message for the status line, until the next key
+    message: Option<String>,
+}
+
+impl<'a> Grid<'a> {
+    fn new(
+        res: &'a QueryResult,
+        rows: &'a [Row],
+        null_string: &'a str,
+        max_width: usize,
+    ) -> Grid<'a> {
+        let mut grid = Grid {
+            res,
+            rows,
+            null_string,
+            widths: vec![],
+            order: (0..rows.len()).collect(),
+            sort: None,
+            row: 0,
+            col: 0,
+            top: 0,
+            left: 1,
+            search: None,
+            message: None,
+        };
+        grid.widths = res
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let values = rows.iter().map(|r| text_width(&grid.text(r, i)));
+                values
+                    .max()
+                    .unwrap_or(0)
+                    .min(max_width)
+                    .max(text_width(&c.name))
+            })
+            .collect();
+        grid
+    }
+
+    /// A value in one line
+    fn text(&self, row: &Row, col: usize) -> String {
+        match &row.data[col] {
+            Some(v) => v.replace('\n', "↵").replace('\t', " "),
+            None => self.null_string.to_string(),
+        }
+    }
+
+    fn selected(&self) -> &'a Row {
+        &self.rows[self.order[self.row]]
+    }
+
+    fn run(&mut self) -> Result<()> {
+        let mut screen = Screen::enter()?;
+        loop {
+            self.draw(&mut screen.out)?;
+            let key = match event::read()? {
+                Event::Key(k) if k.kind != KeyEventKind::Release => k,
+                _ => continue,
+            };
+            self.message = None;
+            if !self.handle(key, &mut screen.out)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Returns false to quit
+    fn handle(&mut self, key: KeyEvent, out: &mut Stdout) -> Result<bool> {
+        let last_row = self.rows.len().saturating_sub(1);
+        let last_col = self.widths.len().saturating_sub(1);
+        let page = (terminal::size()?.1 as usize).saturating_sub(3).max(1);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(false)
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.row = self.row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.row = (self.row + 1).min(last_row),
+            KeyCode::PageUp | KeyCode::Char('b') => self.row = self.row.saturating_sub(page),
+            KeyCode::PageDown | KeyCode::Char(' ') | KeyCode::Char('f') => {
+                self.row = (self.row + page).min(last_row)
+            }
+            KeyCode::Home | KeyCode::Char('g') => self.row = 0,
+            KeyCode::End | KeyCode::Char('G') => self.row = last_row,
+            KeyCode::Left | KeyCode::Char('h') => self.col = self.col.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => self.col = (self.col + 1).min(last_col),
+            KeyCode::Char('0') | KeyCode::Char('^') => self.col = 0,
+            KeyCode::Char('$') => self.col = last_col,
+            KeyCode::Char('/') => {
+                if let Some(s) = self.prompt(out, "/")? {
+                    if !s.is_empty() {
+                        self.search = Some(s.to_lowercase());
+                    }
+                    self.find(true);
+                }
+            }
+            KeyCode::Char('n') => self.find(true),
+            KeyCode::Char('N') => self.find(false),
+            KeyCode::Char('s') => self.sort_by(self.col),
+            KeyCode::Char(':') => {
+                if let Some(s) = self.prompt(out, ":")? {
+                    match s.trim().parse::<usize>() {
+                        Ok(n) if n > 0 => self.row = (n - 1).min(last_row),
+                        _ => self.message = Some(format!("No row {}", s.trim())),
+                    }
+                }
+            }
+            KeyCode::Char('c') | KeyCode::Char('y') => {
+                let row = self.selected();
+                let value = row.data[self.col].clone().unwrap_or_default();
+                // OSC 52 puts it into the clipboard of the terminal, also over SSH
+                write!(out, "\x1b]52;c;{}\x07", BASE64.encode(&value))?;
+                self.message = Some(format!(
+                    "Copied {} of row {}",
+                    self.res.columns[self.col].name,
+                    self.row + 1
+                ));
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Moves to the next (or previous) cell containing the search text
+    fn find(&mut self, forward: bool) {
+        let search = match &self.search {
+            Some(s) => s.clone(),
+            None => return,
+        };
+        let cols = self.widths.len();
+        let cells = self.rows.len() * cols;
+        let current = self.row * cols + self.col;
+        for step in 1..=cells {
+            let i = if forward {
+                (current + step) % cells
+            } else {
+                (current + cells - step % cells) % cells
+            };
+            let row = &self.rows[self.order[i / cols]];
+            if self.text(row, i % cols).to_lowercase().contains(&search) {
+                self.row = i / cols;
+                self.col = i % cols;
+                return;
+            }
+        }
+        self.message = Some(format!("Not found: {}", search));
+    }
+
+    /// Sorts ascending, then descending, then in the original order
+    fn sort_by(&mut self, col: usize) {
+        let selected = self.order[self.row];
+        self.sort = match self.sort {
+            Some((c, false)) if c == col => Some((col, true)),
+            Some((c, true)) if c == col => None,
+            _ => Some((col, false)),
+        };
+        self.order = (0..self.rows.len()).collect();
+        if let Some((col, descending)) = self.sort {
+            let rows = self.rows;
+            self.order.sort_by(|&a, &b| {
+                let o = compare(&rows[a].data[col], &rows[b].data[col]);
+                if descending {
+                    o.reverse()
+                } else {
+                    o
+                }
+            });
+        }
+        self.row = self.order.iter().position(|&i| i == selected).unwrap_or(0);
+    }
+
+    /// The columns shown with their widths: the first one, then those from `left`
+    /// that fit, the last one cut
+    fn visible_columns(&mut self, width: usize) -> Vec<(usize, usize)> {
+        if self.col > 0 && self.col < self.left {
+            self.left = self.col;
+        }
+        // The first column leaves room for the others
+        let first = if self.widths.len() > 1 {
+            self.widths[0].min(width / 3)
+        } else {
+            self.widths[0].min(width)
+        };
+        loop {
+            let mut cols = vec![(0, first)];
+            let mut used = cols[0].1;
+            for i in self.left..self.widths.len() {
+                let rest = width.saturating_sub(used + 3);
+                if rest == 0 {
+                    break;
+                }
+                cols.push((i, self.widths[i].min(rest)));
+                used += self.widths[i] + 3;
+            }
+            // The selected column must be shown in full, unless it does not fit at all
+            let shown = cols
+                .iter()
+                .any(|&(i, w)| i == self.col && (w == self.widths[i] || i == self.left));
+            if self.col == 0 || shown || self.left >= self.col {
+                return cols;
+            }
+            self.left += 1;
+        }
+    }
+
+    fn draw(&mut self, out: &mut Stdout) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        let body = height.saturating_sub(3).max(1);
+        if self.row < self.top {
+            self.top = self.row;
+        } else if self.row >= self.top + body {
+            self.top = self.row + 1 - body;
+        }
+        let cols = self.visible_columns(width);
+
+        queue!(out, Clear(ClearType::All), MoveTo(0, 0))?;
+        let separator = Role::Muted.paint(" │ ").to_string();
+        for (n, &(i, w)) in cols.iter().enumerate() {
+            if n > 0 {
+                queue!(out, Print(&separator))?;
+            }
+            let mut title = self.res.columns[i].name.clone();
+            if let Some((c, descending)) = self.sort {
+                if c == i {
+                    title.push(if descending { '↓' } else { '↑' });
+                }
+            }
+            queue!(out, Print(Role::Title.paint(pad(&title, w))))?;
+        }
+        let line: Vec<String> = cols.iter().map(|&(_, w)| "─".repeat(w)).collect();
+        queue!(
+            out,
+            MoveTo(0, 1),
+            Print(Role::Muted.paint(line.join("─┼─")))
+        )?;
+
+        for (y, pos) in (self.top..self.order.len()).take(body).enumerate() {
+            queue!(out, MoveTo(0, y as u16 + 2))?;
+            let row = &self.rows[self.order[pos]];
+            for (n, &(i, w)) in cols.iter().enumerate() {
+                if n > 0 {
+                    queue!(out, Print(&separator))?;
+                }
+                let text = pad(&self.text(row, i), w);
+                let painted = match (&row.data[i], i) {
+                    (None, _) => Role::Null.paint(text),
+                    (_, 0) => Role::Strong.paint(text),
+                    _ => Role::Value.paint(text),
+                };
+                if pos == self.row && i == self.col {
+                    queue!(
+                        out,
+                        SetAttribute(Attribute::Reverse),
+                        Print(painted),
+                        SetAttribute(Attribute::Reset)
+                    )?;
+                } else {
+                    queue!(out, Print(painted))?;
+                }
+            }
+        }
+
+        let status = match &self.message {
+            Some(m) => m.clone(),
+            None => format!(
+                "row {}/{}  column {}/{}  {}",
+                self.row + 1,
+                self.rows.len(),
+                self.col + 1,
+                self.widths.len(),
+                HELP
+            ),
+        };
+        queue!(
+            out,
+            MoveTo(0, height.saturating_sub(1) as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(pad(&status, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Reads a line in the status line; `None` if cancelled with Esc
+    fn prompt(&mut self, out: &mut Stdout, prefix: &str) -> Result<Option<String>> {
+        let mut input = String::new();
+        loop {
+            let (width, height) = terminal::size()?;
+            queue!(
+                out,
+                MoveTo(0, height.saturating_sub(1)),
+                Clear(ClearType::CurrentLine),
+                Print(fit(
+                    &format!("{}{}", prefix, input),
+                    Some(width as usize),
+                    false
+                )),
+                Show
+            )?;
+            out.flush()?;
+            let key = match event::read()? {
+                Event::Key(k) if k.kind != KeyEventKind::Release => k,
+                _ => continue,
+            };
+            match key.code {
+                KeyCode::Enter => break,
+                KeyCode::Esc => {
+                    queue!(out, Hide)?;
+                    return Ok(None);
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    queue!(out, Hide)?;
+                    return Ok(None);
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+        }
+        queue!(out, Hide)?;
+        Ok(Some(input))
+    }
+}
+
+/// Fills a value up to the width, cutting it if it is wider
+fn pad(s: &str, width: usize) -> String {
+    let s = fit(s, Some(width), false);
+    format!("{}{}", s, " ".repeat(width.saturating_sub(text_width(&s))))
+}
+
+/// Orders numbers by value, everything else as text, NULL last
+fn compare(a: &Option<String>, b: &Option<String>) -> Ordering {
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, _) => Ordering::Greater,
+        (_, None) => Ordering::Less,
+        (Some(a), Some(b)) => match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+            (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+            _ => a.cmp(b),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Column;
+
+    fn result(columns: &[&str], rows: &[&[Option<&str>]]) -> (QueryResult, Vec<Row>) {
+        let res = QueryResult {
+            columns: columns
+                .iter()
+                .map(|c| Column {
+                    name: c.to_string(),
+                })
+                .collect(),
+            rows: vec![],
+        };
+        let rows = rows
+            .iter()
+            .map(|r| Row {
+                data: r.iter().map(|v| v.map(String::from)).collect(),
+            })
+            .collect();
+        (res, rows)
+    }
+
+    #[test]
+    fn compares_numbers_text_and_nulls() {
+        let v = |s: &str| Some(s.to_string());
+        assert_eq!(compare(&v("9"), &v("10")), Ordering::Less);
+        assert_eq!(compare(&v(" 2.5"), &v("-3")), Ordering::Greater);
+        assert_eq!(compare(&v("10"), &v("9a")), Ordering::Less);
+        assert_eq!(compare(&v("abc"), &v("abd")), Ordering::Less);
+        assert_eq!(compare(&None, &v("1")), Ordering::Greater);
+        assert_eq!(compare(&v("1"), &None), Ordering::Less);
+        assert_eq!(compare(&None, &None), Ordering::Equal);
+    }
+
+    #[test]
+    fn finds_cells_in_both_directions() {
+        let (res, rows) = result(
+            &["id", "name"],
+            &[
+                &[Some("1"), Some("Alice")],
+                &[Some("2"), None],
+                &[Some("3"), Some("alina")],
+            ],
+        );
+        let mut grid = Grid::new(&res, &rows, "<null>", 40);
+        grid.search = Some("ali".into());
+        grid.find(true);
+        assert_eq!((grid.row, grid.col), (0, 1));
+        grid.find(true);
+        assert_eq!((grid.row, grid.col), (2, 1));
+        // Wraps around at the end
+        grid.find(true);
+        assert_eq!((grid.row, grid.col), (0, 1));
+        grid.find(false);
+        assert_eq!((grid.row, grid.col), (2, 1));
+
+        grid.search = Some("null".into());
+        grid.find(true);
+        assert_eq!((grid.row, grid.col), (1, 1));
+
+        grid.search = Some("bob".into());
+        grid.find(true);
+        assert_eq!((grid.row, grid.col), (1, 1));
+        assert_eq!(grid.message.as_deref(), Some("Not found: bob"));
+    }
+
+    #[test]
+    fn finds_in_sorted_order() {
+        let (res, rows) = result(&["n"], &[&[Some("b")], &[Some("a")], &[Some("ab")]]);
+        let mut grid = Grid::new(&res, &rows, "", 40);
+        grid.sort_by(0);
+        grid.search = Some("b".into());
+        grid.find(true);
+        assert_eq!(grid.selected().data[0].as_deref(), Some("ab"));
+        grid.find(true);
+        assert_eq!(grid.selected().data[0].as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn wide_first_column_leaves_room() {
+        let wide = "x".repeat(100);
+        let (res, rows) = result(&["a", "b"], &[&[Some(&wide), Some("1")]]);
+        let mut grid = Grid::new(&res, &rows, "", 200);
+        assert_eq!(grid.visible_columns(60), [(0, 20), (1, 1)]);
+
+        let (res, rows) = result(&["a"], &[&[Some(&wide)]]);
+        let mut grid = Grid::new(&res, &rows, "", 200);
+        assert_eq!(grid.visible_columns(60), [(0, 60)]);
+    }
+}
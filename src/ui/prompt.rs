@@ -0,0 +1,116 @@
+//! Prompt templates, like `{user}@{host}/{schema}{tx}> `. Placeholders are
+//! replaced by the state of the connection and the last executed line.
+
+use super::theme::Role;
+use super::ExecutionSummary;
+use crate::database::{Dialect, SessionInfo, TransactionState};
+use anyhow::anyhow;
+use anyhow::Result;
+use colored::{Color, Colorize};
+use regex::{Captures, Regex};
+
+const PLACEHOLDERS: &[&str] = &[
+    "identifier",
+    "backend",
+    "user",
+    "schema",
+    "host",
+    "tx",
+    "duration",
+    "status",
+];
+
+/// Placeholders filled from `Connection::session_info`
+const SESSION_PLACEHOLDERS: &[&str] = &["user", "schema", "host", "tx"];
+
+fn placeholder_re() -> Regex {
+    Regex::new(r"\{(\w+)\}").unwrap()
+}
+
+/// Fails for placeholders that do not exist
+pub fn validate(template: &str) -> Result<()> {
+    for c in placeholder_re().captures_iter(template) {
+        if !PLACEHOLDERS.contains(&&c[1]) {
+            return Err(anyhow!(
+                "Unknown placeholder {} in prompt, known are {}",
+                &c[0],
+                PLACEHOLDERS.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether the session info has to be queried for the template
+pub fn uses_session(template: &str) -> bool {
+    placeholder_re()
+        .captures_iter(template)
+        .any(|c| SESSION_PLACEHOLDERS.contains(&&c[1]))
+}
+
+/// Everything a prompt can show
+pub struct PromptContext<'a> {
+    pub identifier: &'a str,
+    pub dialect: Dialect,
+    pub session: &'a SessionInfo,
+    /// The statements of the last line that executed any
+    pub last: Option<&'a ExecutionSummary>,
+    /// Overrides the theme for the identifier
+    pub identifier_color: Option<Color>,
+}
+
+fn format_duration(summary: &ExecutionSummary) -> String {
+    let ms = summary.duration.as_millis();
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else {
+        format!("{:.1}s", summary.duration.as_secs_f64())
+    }
+}
+
+/// Fills in the template; `production` shows the whole prompt in the production style
+pub fn render(template: &str, ctx: &PromptContext, production: bool) -> String {
+    let value = |name: &str| -> (String, Role) {
+        match name {
+            "identifier" => (ctx.identifier.to_string(), Role::Identifier),
+            "backend" => (ctx.dialect.short_name().to_string(), Role::Backend),
+            "user" => (ctx.session.user.clone(), Role::Value),
+            "schema" => (ctx.session.schema.clone(), Role::Value),
+            "host" => (ctx.session.host.clone(), Role::Muted),
+            "tx" => match ctx.session.transaction {
+                TransactionState::Idle => (String::new(), Role::Warning),
+                TransactionState::Active => ("*".into(), Role::Warning),
+                TransactionState::Failed => ("!".into(), Role::Error),
+            },
+            "duration" => (
+                ctx.last.map(format_duration).unwrap_or_default(),
+                Role::Info,
+            ),
+            "status" => match ctx.last {
+                Some(s) if s.error.is_some() => ("error".into(), Role::Error),
+                Some(_) => ("ok".into(), Role::Success),
+                None => (String::new(), Role::Success),
+            },
+            _ => (String::new(), Role::Muted),
+        }
+    };
+    let re = placeholder_re();
+    if production {
+        let plain = re.replace_all(template, |c: &Captures| value(&c[1]).0);
+        // The trailing space stays unstyled, like in the default prompt
+        let trimmed = plain.trim_end();
+        return format!(
+            "{}{}",
+            Role::Production.paint(trimmed),
+            &plain[trimmed.len()..]
+        );
+    }
+    re.replace_all(template, |c: &Captures| {
+        let (text, role) = value(&c[1]);
+        match (&c[1], ctx.identifier_color) {
+            ("identifier", Some(color)) => text.color(color).to_string(),
+            _ => role.paint(text).to_string(),
+        }
+    })
+    .into_owned()
+}
@@ -0,0 +1,294 @@
+//! The output formats of results: tables fitted into the terminal, and documents
+//! to paste elsewhere, like Markdown for tickets or JSON for scripts.
+
+use super::OutputFormat;
+use crate::database::{QueryResult, Row};
+use anyhow::anyhow;
+use anyhow::Result;
+use csv::WriterBuilder;
+use prettytable::format::{self, FormatBuilder, LinePosition, LineSeparator, TableFormat};
+use serde_yaml::Value;
+use std::io::Write;
+use std::str::FromStr;
+
+impl OutputFormat {
+    /// Whether results are printed as table, fitted into the terminal
+    pub fn is_table(self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Table | OutputFormat::Unicode | OutputFormat::Plain
+        )
+    }
+
+    /// The lines of a table format
+    pub fn table_format(self) -> TableFormat {
+        match self {
+            OutputFormat::Unicode => FormatBuilder::new()
+                .column_separator('│')
+                .borders('│')
+                .separators(&[LinePosition::Top], LineSeparator::new('─', '┬', '┌', '┐'))
+                .separators(
+                    &[LinePosition::Title],
+                    LineSeparator::new('─', '┼', '├', '┤'),
+                )
+                .separators(
+                    &[LinePosition::Bottom],
+                    LineSeparator::new('─', '┴', '└', '┘'),
+                )
+                .padding(1, 1)
+                .build(),
+            OutputFormat::Plain => FormatBuilder::new()
+                .column_separator(' ')
+                .padding(0, 1)
+                .build(),
+            _ => *format::consts::FORMAT_NO_LINESEP_WITH_TITLE,
+        }
+    }
+
+    /// The width of a table with columns of the given widths
+    pub fn table_width(self, columns: &[usize]) -> usize {
+        let content: usize = columns.iter().sum();
+        match self {
+            OutputFormat::Plain => content + (2 * columns.len()).saturating_sub(1),
+            _ => content + 3 * columns.len() + 1,
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<OutputFormat> {
+        serde_yaml::from_value(Value::String(s.to_lowercase())).map_err(|_| {
+            anyhow!(
+                "Unknown format {}, known are table, unicode, plain, markdown, html, latex, csv, tsv and json",
+                s
+            )
+        })
+    }
+}
+
+/// Writes the rows in a format that is no table, with all columns and values in full
+pub fn write_document(
+    format: OutputFormat,
+    res: &QueryResult,
+    rows: &[Row],
+    null_string: &str,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let names: Vec<&str> = res.columns.iter().map(|c| c.name.as_str()).collect();
+    let values = |r: &Row| -> Vec<String> {
+        r.data
+            .iter()
+            .map(|v| v.clone().unwrap_or_else(|| null_string.to_string()))
+            .collect()
+    };
+    match format {
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let delimiter = if format == OutputFormat::Csv {
+                b';'
+            } else {
+                b'\t'
+            };
+            let mut wtr = WriterBuilder::new().delimiter(delimiter).from_writer(out);
+            wtr.write_record(&names)?;
+            for r in rows {
+                wtr.write_record(values(r))?;
+            }
+            wtr.flush()?;
+        }
+        OutputFormat::Markdown => {
+            let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+            let escape = |s: &str| s.replace('|', "\\|").replace('\n', "<br>");
+            writeln!(out, "{}", line(names.iter().map(|n| escape(n)).collect()))?;
+            writeln!(
+                out,
+                "{}",
+                line(names.iter().map(|_| "---".into()).collect())
+            )?;
+            for r in rows {
+                writeln!(
+                    out,
+                    "{}",
+                    line(values(r).iter().map(|v| escape(v)).collect())
+                )?;
+            }
+        }
+        OutputFormat::Html => {
+            let escape = |s: &str| {
+                s.replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;")
+                    .replace('"', "&quot;")
+            };
+            let line = |tag: &str, cells: &[String]| {
+                let cells: String = cells
+                    .iter()
+                    .map(|c| format!("<{0}>{1}</{0}>", tag, escape(c)))
+                    .collect();
+                format!("    <tr>{}</tr>", cells)
+            };
+            writeln!(out, "<table>")?;
+            writeln!(out, "  <thead>")?;
+            let titles: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+            writeln!(out, "{}", line("th", &titles))?;
+            writeln!(out, "  </thead>")?;
+            writeln!(out, "  <tbody>")?;
+            for r in rows {
+                writeln!(out, "{}", line("td", &values(r)))?;
+            }
+            writeln!(out, "  </tbody>")?;
+            writeln!(out, "</table>")?;
+        }
+        OutputFormat::Latex => {
+            let escape = |s: &str| {
+                s.chars()
+                    .map(|c| match c {
+                        '\\' => "\\textbackslash{}".to_string(),
+                        '~' => "\\textasciitilde{}".to_string(),
+                        '^' => "\\textasciicircum{}".to_string(),
+                        '&' | '%' | '$' | '#' | '_' | '{' | '}' => format!("\\{}", c),
+                        c => c.to_string(),
+                    })
+                    .collect::<String>()
+            };
+            let line = |cells: Vec<String>| format!("{} \\\\", cells.join(" & "));
+            writeln!(out, "\\begin{{tabular}}{{{}}}", "l".repeat(names.len()))?;
+            writeln!(out, "\\hline")?;
+            writeln!(out, "{}", line(names.iter().map(|n| escape(n)).collect()))?;
+            writeln!(out, "\\hline")?;
+            for r in rows {
+                writeln!(
+                    out,
+                    "{}",
+                    line(values(r).iter().map(|v| escape(v)).collect())
+                )?;
+            }
+            writeln!(out, "\\hline")?;
+            writeln!(out, "\\end{{tabular}}")?;
+        }
+        OutputFormat::Json => {
+            // Written by hand to keep the order of the columns
+            writeln!(out, "[")?;
+            for (i, r) in rows.iter().enumerate() {
+                let fields: Vec<String> = names
+                    .iter()
+                    .zip(&r.data)
+                    .map(|(n, v)| {
+                        Ok(format!(
+                            "{}: {}",
+                            serde_json::to_string(n)?,
+                            serde_json::to_string(v)?
+                        ))
+                    })
+                    .collect::<Result<_>>()?;
+                let comma = if i + 1 < rows.len() { "," } else { "" };
+                writeln!(out, "  {{{}}}{}", fields.join(", "), comma)?;
+            }
+            writeln!(out, "]")?;
+        }
+        OutputFormat::Table | OutputFormat::Unicode | OutputFormat::Plain => {
+            return Err(anyhow!("{:?} is a table format", format));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Column;
+
+    fn write(format: OutputFormat, columns: &[&str], rows: &[&[Option<&str>]]) -> String {
+        let res = QueryResult {
+            columns: columns
+                .iter()
+                .map(|n| Column {
+                    name: n.to_string(),
+                })
+                .collect(),
+            rows: rows
+                .iter()
+                .map(|r| Row {
+                    data: r.iter().map(|v| v.map(String::from)).collect(),
+                })
+                .collect(),
+        };
+        let mut out = vec![];
+        write_document(format, &res, &res.rows, "NULL", &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn escapes_markdown() {
+        assert_eq!(
+            write(
+                OutputFormat::Markdown,
+                &["a|b", "c"],
+                &[&[Some("x|y"), Some("1\n2")], &[None, Some("")]]
+            ),
+            "| a\\|b | c |\n| --- | --- |\n| x\\|y | 1<br>2 |\n| NULL |  |\n"
+        );
+    }
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            write(
+                OutputFormat::Html,
+                &["<a>"],
+                &[&[Some(r#"Tom & "Jerry" <b>"#)], &[None]]
+            ),
+            "<table>\n  <thead>\n    <tr><th>&lt;a&gt;</th></tr>\n  </thead>\n  <tbody>\n\
+             \x20   <tr><td>Tom &amp; &quot;Jerry&quot; &lt;b&gt;</td></tr>\n\
+             \x20   <tr><td>NULL</td></tr>\n  </tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn escapes_latex() {
+        assert_eq!(
+            write(
+                OutputFormat::Latex,
+                &["a_b", "c"],
+                &[&[Some(r"\ ~ ^ & % $ # _ { }"), None]]
+            ),
+            "\\begin{tabular}{ll}\n\\hline\na\\_b & c \\\\\n\\hline\n\
+             \\textbackslash{} \\textasciitilde{} \\textasciicircum{} \\& \\% \\$ \\# \\_ \\{ \\} \
+             & NULL \\\\\n\\hline\n\\end{tabular}\n"
+        );
+    }
+
+    #[test]
+    fn writes_json_nulls_as_null() {
+        assert_eq!(
+            write(
+                OutputFormat::Json,
+                &["a", "b"],
+                &[&[Some("x\"y"), None], &[Some("1"), Some("NULL")]]
+            ),
+            "[\n  {\"a\": \"x\\\"y\", \"b\": null},\n  {\"a\": \"1\", \"b\": \"NULL\"}\n]\n"
+        );
+        assert_eq!(write(OutputFormat::Json, &["a"], &[]), "[\n]\n");
+    }
+
+    #[test]
+    fn quotes_csv_and_tsv() {
+        assert_eq!(
+            write(
+                OutputFormat::Csv,
+                &["a", "b"],
+                &[&[Some("x;y"), Some("say \"hi\"\n")], &[None, Some("z")]]
+            ),
+            "a;b\n\"x;y\";\"say \"\"hi\"\"\n\"\nNULL;z\n"
+        );
+        assert_eq!(
+            write(
+                OutputFormat::Tsv,
+                &["a", "b"],
+                &[&[Some("x\ty"), Some("x;y")]]
+            ),
+            "a\tb\n\"x\ty\"\tx;y\n"
+        );
+    }
+}
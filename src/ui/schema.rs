@@ -0,0 +1,204 @@
+//! The tables and columns of a connection, used by the completion.
+//!
+//! Tables are loaded in a background thread over a connection of its own, so
+//! that large schemas do not delay the prompt, and kept in
+//! `~/.dbc/cache/<identifier>.json` until they are older than `schema_ttl`.
+
+use crate::database::{create_connection, Connection, ConnectionParams, TableRef};
+use anyhow::Result;
+use chrono::Utc;
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    /// Unix time the tables were loaded
+    loaded_at: i64,
+    tables: Vec<TableRef>,
+    /// Column names by lower case `schema.table`
+    columns: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Default)]
+pub struct SchemaCache {
+    data: CacheFile,
+    /// None if the cache is not kept on disk
+    path: Option<PathBuf>,
+    /// The connection a background load opens; without one, tables are loaded on the main connection
+    source: Option<(String, ConnectionParams)>,
+    /// The result of the background load, until it is taken over
+    pending: Arc<Mutex<Option<Result<Vec<TableRef>>>>>,
+    loading: bool,
+    error: Option<String>,
+    /// Tables whose columns could not be loaded; the hints ask on every key, so they
+    /// are not asked for again until the next refresh
+    failed: BTreeSet<String>,
+}
+
+fn column_key(table: &TableRef) -> String {
+    format!("{}.{}", table.schema, table.name).to_lowercase()
+}
+
+impl SchemaCache {
+    /// The cache of a connection as saved on disk, empty if there is none
+    pub fn open(identifier: &str, source: Option<ConnectionParams>) -> SchemaCache {
+        let path = home_dir().map(|h| {
+            h.join(".dbc")
+                .join("cache")
+                .join(super::file_name(identifier, "json"))
+        });
+        let data = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        SchemaCache {
+            data,
+            path,
+            source: source.map(|p| (identifier.to_string(), p)),
+            ..SchemaCache::default()
+        }
+    }
+
+    /// Whether the tables were loaded longer than `ttl` ago; without a ttl they always are
+    pub fn is_stale(&self, ttl: Option<Duration>) -> bool {
+        match ttl {
+            Some(ttl) => Utc::now().timestamp() - self.data.loaded_at > ttl.as_secs() as i64,
+            None => true,
+        }
+    }
+
+    fn is_persistent(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Reloads the tables, in the background if possible, and forgets all columns
+    pub fn refresh(&mut self, conn: &mut dyn Connection) -> Result<()> {
+        self.data.columns.clear();
+        self.failed.clear();
+        match &self.source {
+            Some((identifier, params)) => {
+                if self.loading {
+                    return Ok(());
+                }
+                self.loading = true;
+                let (identifier, params) = (identifier.clone(), params.clone());
+                let pending = self.pending.clone();
+                thread::spawn(move || {
+                    let res =
+                        create_connection(&identifier, params).and_then(|mut c| c.list_tables());
+                    *pending.lock().unwrap() = Some(res);
+                });
+            }
+            None => {
+                self.data.tables = conn.list_tables()?;
+                self.data.loaded_at = Utc::now().timestamp();
+                self.save();
+            }
+        }
+        Ok(())
+    }
+
+    /// Called after the schema was changed; only a cache kept on disk is reloaded right away
+    pub fn schema_changed(&mut self, conn: &mut dyn Connection) -> Result<()> {
+        if self.is_persistent() {
+            self.refresh(conn)
+        } else {
+            self.data.columns.clear();
+            self.failed.clear();
+            Ok(())
+        }
+    }
+
+    /// Takes over the tables of a finished background load
+    fn poll(&mut self) {
+        if let Some(res) = self.pending.lock().unwrap().take() {
+            self.loading = false;
+            match res {
+                Ok(tables) => {
+                    self.data.tables = tables;
+                    self.data.loaded_at = Utc::now().timestamp();
+                    self.save();
+                }
+                Err(e) => self.error = Some(format!("{:#}", e)),
+            }
+        }
+    }
+
+    /// Why the last background load failed, reported once
+    pub fn take_error(&mut self) -> Option<String> {
+        self.poll();
+        self.error.take()
+    }
+
+    pub fn tables(&mut self) -> &[TableRef] {
+        self.poll();
+        &self.data.tables
+    }
+
+    /// The columns of a table, loaded with `load` on first use
+    pub fn columns<F>(&mut self, table: &TableRef, load: &mut F) -> &[String]
+    where
+        F: FnMut(&TableRef) -> Option<Vec<String>>,
+    {
+        let key = column_key(table);
+        if self.failed.contains(&key) {
+            return &[];
+        }
+        if !self.data.columns.contains_key(&key) {
+            match load(table) {
+                Some(c) => self.data.columns.insert(key.clone(), c),
+                None => {
+                    self.failed.insert(key);
+                    return &[];
+                }
+            };
+            self.save();
+        }
+        &self.data.columns[&key]
+    }
+
+    /// Writes the cache to disk; it is only an optimization, so failures are ignored
+    fn save(&self) {
+        if let Some(p) = &self.path {
+            if let Some(d) = p.parent() {
+                let _ = fs::create_dir_all(d);
+            }
+            if let Ok(s) = serde_json::to_string(&self.data) {
+                let _ = fs::write(p, s);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remembers_failed_column_loads() {
+        let mut cache = SchemaCache::default();
+        let table = TableRef {
+            schema: "".into(),
+            name: "t".into(),
+        };
+        let mut calls = 0;
+        let mut fail = |_: &TableRef| {
+            calls += 1;
+            None
+        };
+        assert!(cache.columns(&table, &mut fail).is_empty());
+        assert!(cache.columns(&table, &mut fail).is_empty());
+        assert_eq!(calls, 1);
+
+        cache.failed.clear();
+        let mut load = |_: &TableRef| Some(vec!["id".to_string()]);
+        assert_eq!(cache.columns(&table, &mut load), ["id"]);
+    }
+}
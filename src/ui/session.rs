@@ -0,0 +1,80 @@
+//! The connections open in one run of dbc. Each has its own settings, schema
+//! cache and history; input goes to the active one, chosen with `:use`.
+
+use super::history::History;
+use super::schema::SchemaCache;
+use super::DbcClient;
+use crate::config::Config;
+use crate::database::{create_connection, resolve_params, Connection, Dialect};
+use anyhow::Result;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub struct Session {
+    /// How the connection is referred to by `:use`, the identifier unless given
+    pub name: String,
+    pub identifier: String,
+    /// Shared with the completion, which loads columns while a line is edited
+    pub conn: Rc<RefCell<Box<dyn Connection>>>,
+    pub client: DbcClient,
+    pub schema: Rc<RefCell<SchemaCache>>,
+    pub history: History,
+    pub query_completions: Vec<String>,
+}
+
+impl Session {
+    /// Connects and applies the settings of the connection; `cache` loads the
+    /// schema cache from disk and refreshes it when stale
+    pub fn open(config: &Config, identifier: &str, name: &str, cache: bool) -> Result<Session> {
+        let params = resolve_params(identifier, config.connection(identifier)?)?;
+        let settings = config.settings_for(identifier);
+        let mut client = DbcClient::default();
+        client.options.production = params.has_tag("production");
+        client.options.confirm_writes = client.options.production;
+        if name != identifier {
+            client.name = Some(name.to_string());
+        }
+
+        let mut conn = create_connection(identifier, params.clone())?;
+        conn.print_connection_info()?;
+        client.options.autocommit = conn.autocommit();
+        crate::commands::settings::apply_settings(&mut client, &mut conn, &settings)?;
+        conn.set_user_queries(config.queries_for(identifier, conn.dialect())?);
+
+        let schema = if cache {
+            // Another connection to an in-memory SQLite database would not see its tables
+            let source = if conn.dialect() == Dialect::Sqlite {
+                None
+            } else {
+                Some(params)
+            };
+            let mut schema = SchemaCache::open(identifier, source);
+            if schema.is_stale(client.options.schema_ttl) {
+                schema.refresh(conn.as_mut())?;
+            }
+            schema
+        } else {
+            SchemaCache::default()
+        };
+
+        let query_completions = conn
+            .standard_queries()
+            .iter()
+            .map(|q| q.name.to_string())
+            .collect();
+
+        Ok(Session {
+            name: name.to_string(),
+            identifier: identifier.to_string(),
+            conn: Rc::new(RefCell::new(conn)),
+            client,
+            schema: Rc::new(RefCell::new(schema)),
+            history: History::open(identifier)?,
+            query_completions,
+        })
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        self.conn.borrow().dialect()
+    }
+}
@@ -0,0 +1,398 @@
+//! Colors of everything dbc prints. Output is painted by the role it plays, like
+//! an error or a column title, and the active theme decides the style of each role.
+//!
+//! Besides the built-in themes, themes can be defined in a top-level `themes:`
+//! block of the config, based on another theme. No colors are used at all when
+//! `NO_COLOR` is set or the output is no terminal.
+
+use anyhow::anyhow;
+use anyhow::Result;
+use colored::control::SHOULD_COLORIZE;
+use colored::{Color, ColoredString, Colorize};
+use prettytable::{color, Attr, Cell, Table};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+/// What a piece of output is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Error,
+    Warning,
+    Success,
+    /// Headings of sections, like `Columns:`
+    Heading,
+    /// Column titles of tables
+    Title,
+    Null,
+    /// Results of statements without rows, like `3 rows updated.`
+    Feedback,
+    /// Secondary information, like server notices and plan details
+    Detail,
+    /// Timings, hints and the lines of trees
+    Info,
+    /// Less important parts, like times in the history
+    Muted,
+    /// Connection identifiers
+    Identifier,
+    /// The database type in the prompt
+    Backend,
+    /// The prompt of connections tagged `production`
+    Production,
+    /// Values worth noticing, like versions and object names
+    Value,
+    /// Markers of problems, like the failing position in a statement
+    Mark,
+    Strong,
+    // The input line
+    Keyword,
+    Name,
+    String,
+    Number,
+    Comment,
+    Operator,
+    BindVariable,
+    Command,
+    /// Unterminated quotes and unbalanced parentheses
+    Invalid,
+}
+
+impl Role {
+    /// The style of the role in the default theme
+    fn default_style(self) -> Style {
+        let s = |fg| Style {
+            fg: Some(fg),
+            ..Style::default()
+        };
+        match self {
+            Role::Error => s(Color::Red),
+            Role::Warning | Role::Value | Role::String => s(Color::Yellow),
+            Role::Success | Role::Keyword => s(Color::Green),
+            Role::Heading | Role::Null | Role::Feedback | Role::Backend | Role::Number => {
+                s(Color::Magenta)
+            }
+            Role::Title => Style {
+                bold: true,
+                ..s(Color::Green)
+            },
+            Role::Detail | Role::Identifier | Role::Name => s(Color::Cyan),
+            Role::Info => s(Color::Blue),
+            Role::Muted | Role::Comment => Style {
+                dimmed: true,
+                ..Style::default()
+            },
+            Role::Production => Style {
+                bg: Some(Color::Red),
+                bold: true,
+                ..s(Color::White)
+            },
+            Role::Mark => Style {
+                bold: true,
+                ..s(Color::Red)
+            },
+            Role::Strong | Role::Operator => Style {
+                bold: true,
+                ..Style::default()
+            },
+            Role::BindVariable => s(Color::BrightMagenta),
+            Role::Command => Style {
+                bold: true,
+                ..s(Color::Blue)
+            },
+            Role::Invalid => Style {
+                bg: Some(Color::Red),
+                ..s(Color::White)
+            },
+        }
+    }
+
+    fn style(self) -> Style {
+        THEME
+            .read()
+            .unwrap()
+            .get(&self)
+            .copied()
+            .unwrap_or_else(|| self.default_style())
+    }
+
+    pub fn paint<S: AsRef<str>>(self, text: S) -> ColoredString {
+        self.style().paint(text.as_ref())
+    }
+
+    /// A table cell in the style of the role
+    pub fn cell(self, text: &str) -> Cell {
+        self.style()
+            .attrs()
+            .into_iter()
+            .fold(Cell::new(text), |c, a| c.with_style(a))
+    }
+}
+
+/// Colors and attributes, written like `bold white on red`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Style {
+    fg: Option<Color>,
+    bg: Option<Color>,
+    bold: bool,
+    dimmed: bool,
+    italic: bool,
+    underline: bool,
+    reversed: bool,
+}
+
+impl Style {
+    fn paint(&self, text: &str) -> ColoredString {
+        let mut s = text.normal();
+        if let Some(c) = self.fg {
+            s = s.color(c);
+        }
+        if let Some(c) = self.bg {
+            s = s.on_color(c);
+        }
+        if self.bold {
+            s = s.bold();
+        }
+        if self.dimmed {
+            s = s.dimmed();
+        }
+        if self.italic {
+            s = s.italic();
+        }
+        if self.underline {
+            s = s.underline();
+        }
+        if self.reversed {
+            s = s.reversed();
+        }
+        s
+    }
+
+    /// The style for prettytable, which does not know about `NO_COLOR`
+    fn attrs(&self) -> Vec<Attr> {
+        if !SHOULD_COLORIZE.should_colorize() {
+            return vec![];
+        }
+        let mut attrs = vec![];
+        if let Some(c) = self.fg.and_then(term_color) {
+            attrs.push(Attr::ForegroundColor(c));
+        }
+        if let Some(c) = self.bg.and_then(term_color) {
+            attrs.push(Attr::BackgroundColor(c));
+        }
+        if self.bold {
+            attrs.push(Attr::Bold);
+        }
+        if self.dimmed {
+            attrs.push(Attr::Dim);
+        }
+        if self.italic {
+            attrs.push(Attr::Italic(true));
+        }
+        if self.underline {
+            attrs.push(Attr::Underline(true));
+        }
+        if self.reversed {
+            attrs.push(Attr::Reverse);
+        }
+        attrs
+    }
+}
+
+fn term_color(c: Color) -> Option<color::Color> {
+    Some(match c {
+        Color::Black => color::BLACK,
+        Color::Red => color::RED,
+        Color::Green => color::GREEN,
+        Color::Yellow => color::YELLOW,
+        Color::Blue => color::BLUE,
+        Color::Magenta => color::MAGENTA,
+        Color::Cyan => color::CYAN,
+        Color::White => color::WHITE,
+        Color::BrightBlack => color::BRIGHT_BLACK,
+        Color::BrightRed => color::BRIGHT_RED,
+        Color::BrightGreen => color::BRIGHT_GREEN,
+        Color::BrightYellow => color::BRIGHT_YELLOW,
+        Color::BrightBlue => color::BRIGHT_BLUE,
+        Color::BrightMagenta => color::BRIGHT_MAGENTA,
+        Color::BrightCyan => color::BRIGHT_CYAN,
+        Color::BrightWhite => color::BRIGHT_WHITE,
+        Color::TrueColor { .. } => return None,
+    })
+}
+
+impl FromStr for Style {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Style> {
+        let mut style = Style::default();
+        // `bright` belongs to the following color, `on` makes it the background
+        let mut words = s.split(|c: char| c.is_whitespace() || c == '_');
+        while let Some(w) = words.next() {
+            match w.to_lowercase().as_str() {
+                "" | "plain" | "normal" => {}
+                "bold" => style.bold = true,
+                "dimmed" | "dim" => style.dimmed = true,
+                "italic" => style.italic = true,
+                "underline" => style.underline = true,
+                "reversed" | "reverse" => style.reversed = true,
+                "on" => {
+                    let c = words.next().ok_or_else(|| anyhow!("No color after on"))?;
+                    let c = match c {
+                        "bright" => format!("bright {}", words.next().unwrap_or_default()),
+                        c => c.to_string(),
+                    };
+                    style.bg = Some(parse_color(&c)?);
+                }
+                "bright" => {
+                    let c = format!("bright {}", words.next().unwrap_or_default());
+                    style.fg = Some(parse_color(&c)?);
+                }
+                c => style.fg = Some(parse_color(c)?),
+            }
+        }
+        Ok(style)
+    }
+}
+
+fn parse_color(s: &str) -> Result<Color> {
+    s.parse().map_err(|_| anyhow!("Unknown color {}", s))
+}
+
+/// A theme from the config, the style of each role given by its name
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "Mapping")]
+pub struct ThemeDefinition {
+    /// The theme providing the styles of the roles not given, `default` if none
+    pub base: Option<String>,
+    pub styles: BTreeMap<Role, Style>,
+}
+
+impl TryFrom<Mapping> for ThemeDefinition {
+    type Error = anyhow::Error;
+
+    fn try_from(m: Mapping) -> Result<ThemeDefinition> {
+        let mut base = None;
+        let mut styles = BTreeMap::new();
+        for (k, v) in m {
+            // YAML reads an unquoted `null` key as null
+            let k = match k {
+                Value::Null => "null".to_string(),
+                Value::String(s) => s,
+                k => return Err(anyhow!("Invalid role {:?}", k)),
+            };
+            let v = match v {
+                Value::String(s) => s,
+                v => return Err(anyhow!("Invalid style {:?} of {}", v, k)),
+            };
+            if k == "base" {
+                base = Some(v);
+                continue;
+            }
+            let role = serde_yaml::from_value(Value::String(k.clone()))
+                .map_err(|_| anyhow!("Unknown role {}", k))?;
+            styles.insert(role, v.parse()?);
+        }
+        Ok(ThemeDefinition { base, styles })
+    }
+}
+
+/// The styles of the active theme that differ from the default theme
+static THEME: RwLock<BTreeMap<Role, Style>> = RwLock::new(BTreeMap::new());
+static USER_THEMES: RwLock<BTreeMap<String, ThemeDefinition>> = RwLock::new(BTreeMap::new());
+
+pub const BUILTIN_THEMES: &[&str] = &["default", "light", "mono", "none"];
+
+fn builtin_theme(name: &str) -> Option<Vec<(Role, &'static str)>> {
+    match name {
+        "default" | "none" => Some(vec![]),
+        // Yellow and cyan are hard to read on white
+        "light" => Some(vec![
+            (Role::Warning, "blue"),
+            (Role::Value, "blue"),
+            (Role::String, "blue"),
+            (Role::Detail, "magenta"),
+            (Role::Identifier, "bold"),
+            (Role::Name, "plain"),
+            (Role::Info, "bright black"),
+            (Role::Keyword, "bold green"),
+            (Role::BindVariable, "magenta"),
+        ]),
+        "mono" => Some(vec![
+            (Role::Error, "bold"),
+            (Role::Warning, "bold"),
+            (Role::Success, "plain"),
+            (Role::Heading, "bold"),
+            (Role::Title, "bold"),
+            (Role::Null, "dimmed"),
+            (Role::Feedback, "plain"),
+            (Role::Detail, "plain"),
+            (Role::Info, "dimmed"),
+            (Role::Identifier, "bold"),
+            (Role::Backend, "plain"),
+            (Role::Production, "reversed"),
+            (Role::Value, "underline"),
+            (Role::Mark, "bold"),
+            (Role::Keyword, "bold"),
+            (Role::Name, "plain"),
+            (Role::String, "plain"),
+            (Role::Number, "plain"),
+            (Role::BindVariable, "underline"),
+            (Role::Command, "bold"),
+            (Role::Invalid, "reversed"),
+        ]),
+        _ => None,
+    }
+}
+
+/// Makes the themes of the config available
+pub fn define_themes(themes: BTreeMap<String, ThemeDefinition>) {
+    *USER_THEMES.write().unwrap() = themes;
+}
+
+/// The styles of a theme, on top of its base
+fn resolve(name: &str, depth: usize) -> Result<BTreeMap<Role, Style>> {
+    if depth > 10 {
+        return Err(anyhow!("Theme {} is based on itself", name));
+    }
+    if let Some(styles) = builtin_theme(name) {
+        return Ok(styles
+            .into_iter()
+            .map(|(r, s)| (r, s.parse().unwrap()))
+            .collect());
+    }
+    let def = USER_THEMES
+        .read()
+        .unwrap()
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("Unknown theme {}", name))?;
+    let mut styles = resolve(def.base.as_deref().unwrap_or("default"), depth + 1)?;
+    styles.extend(def.styles);
+    Ok(styles)
+}
+
+/// Switches to another theme; `none` turns colors off
+pub fn use_theme(name: &str) -> Result<()> {
+    let styles = resolve(name, 0)?;
+    if name == "none" {
+        colored::control::set_override(false);
+    } else {
+        colored::control::unset_override();
+    }
+    *THEME.write().unwrap() = styles;
+    Ok(())
+}
+
+/// Prints a table; prettytable writes escape codes to terminals even for cells without style
+pub fn print_table(table: &Table) {
+    if SHOULD_COLORIZE.should_colorize() {
+        table.printstd();
+    } else {
+        let _ = table.print(&mut io::stdout());
+    }
+}